@@ -1,54 +1,310 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use poi_staking::{
+    epoch_at, SlashReason, StakeAccount, StakeSnapshot, SubnetStakeConfig, EPOCH_DURATION,
+    MAX_SNAPSHOT_VALIDATORS,
+};
 
-declare_id!("poiConsensus111111111111111111111111");
+declare_id!("poiConsensus1111111111111111111111111111111");
+
+/// Registry program that owns Neuron accounts
+pub const REGISTRY_PROGRAM_ID: Pubkey = pubkey!("iJUv5HxvwXFZaGeNDEG1DCNWYNfLQke8SBGvkrKYP2u");
+
+/// Minimum slots between two weight submissions from the same validator
+pub const WEIGHTS_RATE_LIMIT: u64 = 100;
+
+/// Validator submissions per epoch
+pub const MAX_SUBMISSIONS: usize = 64;
+
+/// Weight entries per submission
+pub const MAX_WEIGHTS: usize = 256;
+
+/// Miner UIDs that can be weighted (registry UIDs stay below 256)
+pub const MAX_CONSENSUS_MINERS: usize = 256;
+
+/// Consecutive miner UIDs whose votes and consensus share a `MinerPage`
+pub const MINERS_PER_PAGE: usize = 16;
+
+/// Miner pages covering every UID that can be weighted
+pub const MAX_MINER_PAGES: usize = MAX_CONSENSUS_MINERS / MINERS_PER_PAGE;
+
+/// Seconds at the start of each epoch open for weight commits (half the epoch)
+pub const COMMIT_PHASE_DURATION: i64 = EPOCH_DURATION / 2;
+
+/// Basis point denominator for consensus settings
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Fixed-point one for consensus in both modes: normalized weights,
+/// consensus, rank, trust, incentive, bonds and dividends are all fractions
+/// of `CONSENSUS_PRECISION`
+pub const CONSENSUS_PRECISION: u64 = 1_000_000_000;
+
+/// Default share of stake that must back a weight for it to count (κ)
+pub const DEFAULT_KAPPA_BPS: u64 = 5_000;
+
+/// Default weight of the current epoch in the bond moving average
+pub const DEFAULT_BOND_ALPHA_BPS: u64 = 1_000;
+
+/// Epochs a finalized epoch's accounts are kept for emissions and slashing
+/// before anyone can close them
+pub const CONSENSUS_RETENTION_EPOCHS: u64 = 20;
+
+/// Trust below which a revealed validator's weights deviate from consensus
+pub const SLASH_TRUST_THRESHOLD: u64 = CONSENSUS_PRECISION / 2;
+
+/// Share of stake slashed for weights that deviate from consensus
+pub const DEVIATION_SLASH_BPS: u16 = 100;
+
+/// Share of stake slashed for committing weights without revealing them
+pub const UNREVEALED_SLASH_BPS: u16 = 100;
 
 #[program]
 pub mod poi_consensus {
     use super::*;
 
-    /// Submit weights for miners in a subnet epoch
-    pub fn submit_weights(
-        ctx: Context<SubmitWeights>,
+    /// Choose how a subnet's epochs are finalized. Only the subnet governor can
+    /// configure it; each epoch snapshots the settings when its finalization
+    /// starts.
+    pub fn configure_consensus(
+        ctx: Context<ConfigureConsensus>,
+        subnet_id: u16,
+        mode: ConsensusMode,
+        kappa_bps: u64,
+        bond_alpha_bps: u64,
+    ) -> Result<()> {
+        require!(
+            poi_staking::subnet_governor(&ctx.accounts.subnet)? == ctx.accounts.governor.key(),
+            ConsensusError::Unauthorized
+        );
+        require!(
+            kappa_bps <= BPS_DENOMINATOR && bond_alpha_bps <= BPS_DENOMINATOR,
+            ConsensusError::InvalidConsensusConfig
+        );
+
+        let config = &mut ctx.accounts.consensus_config;
+        config.subnet_id = subnet_id;
+        config.mode = mode;
+        config.kappa_bps = kappa_bps;
+        config.bond_alpha_bps = bond_alpha_bps;
+
+        msg!("Subnet {} consensus configured: {:?}, kappa {}, bond alpha {}", 
+             subnet_id, mode, kappa_bps, bond_alpha_bps);
+
+        Ok(())
+    }
+
+    /// Commit to weights for a subnet epoch during its commit phase. The
+    /// signer must be the hotkey of `validator_uid` and hold a validator
+    /// permit. A validator has one submission per epoch: recommitting
+    /// replaces it, at most once every `WEIGHTS_RATE_LIMIT` slots.
+    pub fn commit_weights(
+        ctx: Context<CommitWeights>,
         subnet_id: u16,
         epoch: u64,
         validator_uid: u16,
-        weights: Vec<WeightEntry>,
+        commit_hash: [u8; 32],
     ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            epoch_phase(epoch, clock.unix_timestamp) == EpochPhase::Commit,
+            ConsensusError::WrongPhase
+        );
+
+        verify_validator(
+            &ctx.accounts.neuron,
+            &ctx.accounts.validator_signer.key(),
+            subnet_id,
+            validator_uid,
+        )?;
+
         let consensus_state = &mut ctx.accounts.consensus_state;
 
+        // The PDA seeds bind both accounts to this subnet and epoch
+        consensus_state.subnet_id = subnet_id;
+        consensus_state.epoch = epoch;
+        if consensus_state.payer == Pubkey::default() {
+            consensus_state.payer = ctx.accounts.validator_signer.key();
+        }
+        require!(
+            consensus_state.finalized == false,
+            ConsensusError::EpochFinalized
+        );
+
+        let submission = &mut ctx.accounts.submission;
+        if submission.validator == Pubkey::default() {
+            // First commit this epoch
+            require!(
+                (consensus_state.submission_count as usize) < MAX_SUBMISSIONS,
+                ConsensusError::SubmissionsFull
+            );
+            consensus_state.submission_count += 1;
+        } else {
+            require!(
+                clock.slot >= submission.last_update_slot + WEIGHTS_RATE_LIMIT,
+                ConsensusError::WeightsRateLimited
+            );
+        }
+
+        // Store weight commitment
+        submission.subnet_id = subnet_id;
+        submission.epoch = epoch;
+        submission.validator_uid = validator_uid;
+        submission.validator = ctx.accounts.validator_signer.key();
+        submission.weights = Vec::new();
+        submission.timestamp = clock.unix_timestamp;
+        submission.last_update_slot = clock.slot;
+        submission.commit_hash = commit_hash;
+        submission.revealed = false;
+
+        // Fixed now, so stake added after the epoch ends never counts
+        let config = poi_staking::load_stake_config(&ctx.accounts.stake_config)?;
+        let snapshot = load_stake_snapshot(&ctx.accounts.stake_snapshot)?;
+        submission.stake_weight = get_validator_stake_weight(
+            &submission.validator,
+            &config,
+            snapshot.as_ref(),
+            &ctx.accounts.stake_account,
+        )?;
+
+        let bonds = &mut ctx.accounts.bonds;
+        if bonds.payer == Pubkey::default() {
+            bonds.payer = ctx.accounts.validator_signer.key();
+        }
+        bonds.subnet_id = subnet_id;
+        bonds.validator_uid = validator_uid;
+        // Bonds belong to the hotkey, not the UID it was registered under
+        if bonds.validator != ctx.accounts.validator_signer.key() {
+            bonds.validator = ctx.accounts.validator_signer.key();
+            bonds.bonds.clear();
+        }
+
+        msg!("Validator {} committed weights for epoch {} in subnet {} at slot {}", 
+             validator_uid, epoch, subnet_id, clock.slot);
+
+        Ok(())
+    }
+
+    /// Reveal committed weights during the epoch's reveal phase. `weights` and
+    /// `salt` must hash to the commitment (see `weights_commitment`), weights
+    /// are sorted by miner UID, and every miner's registry Neuron is passed in
+    /// `remaining_accounts`.
+    pub fn reveal_weights(
+        ctx: Context<RevealWeights>,
+        subnet_id: u16,
+        epoch: u64,
+        validator_uid: u16,
+        weights: Vec<WeightEntry>,
+        salt: [u8; 32],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
         require!(
-            consensus_state.subnet_id == subnet_id,
-            ConsensusError::InvalidSubnet
+            epoch_phase(epoch, clock.unix_timestamp) == EpochPhase::Reveal,
+            ConsensusError::WrongPhase
         );
+
+        verify_validator(
+            &ctx.accounts.neuron,
+            &ctx.accounts.validator_signer.key(),
+            subnet_id,
+            validator_uid,
+        )?;
+
+        let mut registered_uids = Vec::with_capacity(ctx.remaining_accounts.len());
+        for miner_info in ctx.remaining_accounts {
+            let miner = read_neuron(miner_info)?;
+            require!(
+                miner.uid != 0 && miner.subnet_id == subnet_id,
+                ConsensusError::InvalidNeuron
+            );
+            registered_uids.push(miner.uid);
+        }
         require!(
-            consensus_state.epoch == epoch,
-            ConsensusError::InvalidEpoch
+            weights.iter().all(|w| registered_uids.contains(&w.miner_uid)),
+            ConsensusError::UnregisteredMiner
+        );
+        require!(weights.len() <= MAX_WEIGHTS, ConsensusError::TooManyWeights);
+        require!(
+            weights.iter().all(|w| (w.miner_uid as usize) < MAX_CONSENSUS_MINERS),
+            ConsensusError::UnregisteredMiner
         );
+        // One weight per miner, so each validator votes once in a miner page
+        require!(
+            weights.windows(2).all(|w| w[0].miner_uid < w[1].miner_uid),
+            ConsensusError::UnsortedWeights
+        );
+
+        let consensus_state = &mut ctx.accounts.consensus_state;
         require!(
             consensus_state.finalized == false,
             ConsensusError::EpochFinalized
         );
 
-        // Store weight submission
-        let submission = WeightSubmission {
-            validator_uid,
-            subnet_id,
-            epoch,
-            weights: weights.clone(),
-            timestamp: Clock::get()?.unix_timestamp,
-        };
+        let submission = &mut ctx.accounts.submission;
+        require!(!submission.revealed, ConsensusError::AlreadyRevealed);
+        require!(
+            weights_commitment(&submission.validator, subnet_id, epoch, &weights, &salt)
+                == submission.commit_hash,
+            ConsensusError::CommitMismatch
+        );
 
-        consensus_state.submissions.push(submission);
+        submission.weights = weights;
+        submission.revealed = true;
+        submission.last_update_slot = clock.slot;
+        consensus_state.revealed_count += 1;
 
-        msg!("Validator {} submitted weights for epoch {} in subnet {}", 
+        msg!("Validator {} revealed weights for epoch {} in subnet {}", 
              validator_uid, epoch, subnet_id);
 
         Ok(())
     }
 
-    /// Finalize consensus by computing weighted median
-    pub fn finalize_consensus(
-        ctx: Context<FinalizeConsensus>,
+    /// Create miner page `page` of an epoch for finalization to collect votes
+    /// into. Any keeper can open one and pays its rent, which grows as votes
+    /// are accumulated.
+    pub fn open_miner_page(
+        ctx: Context<OpenMinerPage>,
+        subnet_id: u16,
+        epoch: u64,
+        page: u8,
+    ) -> Result<()> {
+        require!((page as usize) < MAX_MINER_PAGES, ConsensusError::InvalidMinerPage);
+        require!(
+            ctx.accounts.consensus_state.finalized == false,
+            ConsensusError::EpochFinalized
+        );
+
+        let miner_page = &mut ctx.accounts.miner_page;
+        miner_page.subnet_id = subnet_id;
+        miner_page.epoch = epoch;
+        miner_page.page = page;
+        miner_page.payer = ctx.accounts.authority.key();
+
+        msg!("Opened miner page {} for epoch {} in subnet {}", page, epoch, subnet_id);
+
+        Ok(())
+    }
+
+    /// Advance consensus finalization by one step, once the epoch's reveal
+    /// phase has closed. Any keeper can crank it; progress is kept in the
+    /// epoch header and the epoch flips to finalized after the last step.
+    ///
+    /// - `Accumulate`: pass a batch of the epoch's `WeightSubmissionAccount`s
+    ///   and the `MinerPage`s their weights fall in (see `open_miner_page`)
+    ///   in `remaining_accounts`. Stake is the weight each submission fixed
+    ///   when it was committed. A submission's weights are added to each
+    ///   page the first time both are passed together, so submissions and
+    ///   pages can be spread over many steps.
+    /// - `Consensus`: pass miner pages to compute their miners' consensus
+    ///   from the votes collected in them
+    /// - `Trust`: pass a batch of submissions to score their validators,
+    ///   followed by the miner pages holding the consensus of every miner
+    ///   they weighted. In Yuma mode each submission is followed by the
+    ///   validator's `ValidatorBonds` account, which is updated, and the pages
+    ///   of the miners it holds bonds in are needed too.
+    pub fn finalize_consensus<'info>(
+        ctx: Context<'_, '_, '_, 'info, FinalizeConsensus<'info>>,
         subnet_id: u16,
         epoch: u64,
     ) -> Result<()> {
@@ -62,239 +318,1244 @@ pub mod poi_consensus {
             consensus_state.finalized == false,
             ConsensusError::EpochFinalized
         );
+        require!(
+            epoch_phase(epoch, Clock::get()?.unix_timestamp) == EpochPhase::Closed,
+            ConsensusError::WrongPhase
+        );
 
-        // Compute weighted median consensus for each miner
-        let miner_consensus = Self::compute_weighted_median(
-            &consensus_state.submissions,
-            &ctx.accounts.stake_accounts,
-        )?;
+        match consensus_state.finalize_stage {
+            FinalizeStage::Accumulate => {
+                if consensus_state.validator_stakes.is_empty() {
+                    let consensus_config = load_consensus_config(&ctx.accounts.consensus_config)?;
+                    consensus_state.mode = consensus_config.mode;
+                    consensus_state.kappa_bps = consensus_config.kappa_bps;
+                    consensus_state.bond_alpha_bps = consensus_config.bond_alpha_bps;
+                }
 
-        // Calculate trust scores
-        let trust_scores = Self::calculate_trust_scores(
-            &consensus_state.submissions,
-            &miner_consensus,
-        )?;
+                accumulate_submissions(
+                    consensus_state,
+                    ctx.remaining_accounts,
+                    &ctx.accounts.authority.to_account_info(),
+                    &ctx.accounts.system_program.to_account_info(),
+                )?;
 
-        // Update consensus state
-        consensus_state.miner_consensus = miner_consensus.clone();
-        consensus_state.validator_consensus = trust_scores.validator_trust;
-        consensus_state.finalized = true;
-        consensus_state.finalized_at = Clock::get()?.unix_timestamp;
+                let accumulated = consensus_state.validator_stakes.len() == consensus_state.submission_count as usize
+                    && consensus_state.validator_stakes.iter().all(|v| v.absorbed_pages == v.pages);
+                if accumulated {
+                    consensus_state.finalize_stage = FinalizeStage::Consensus;
+                }
+            }
+            FinalizeStage::Consensus => {
+                compute_page_consensus(consensus_state, ctx.remaining_accounts)?;
+            }
+            FinalizeStage::Trust => {
+                compute_trust_batch(consensus_state, ctx.remaining_accounts)?;
+            }
+        }
+
+        // A stage can complete as soon as it is entered, e.g. with no reveals
+        if consensus_state.finalize_stage == FinalizeStage::Consensus
+            && consensus_state.computed_pages == consensus_state.miner_pages
+        {
+            consensus_state.finalize_stage = FinalizeStage::Trust;
+        }
+
+        if consensus_state.finalize_stage == FinalizeStage::Trust
+            && consensus_state.validator_consensus.len() == consensus_state.submission_count as usize
+        {
+            if consensus_state.mode == ConsensusMode::Yuma {
+                let total_dividends: u128 = consensus_state.validator_consensus.iter().map(|e| e.dividends as u128).sum();
+                for entry in consensus_state.validator_consensus.iter_mut() {
+                    entry.dividends = fraction(entry.dividends as u128, total_dividends);
+                }
+            }
+
+            consensus_state.finalized = true;
+            consensus_state.finalized_at = Clock::get()?.unix_timestamp;
+
+            msg!("Consensus finalized for epoch {} in subnet {}", epoch, subnet_id);
+        }
 
-        msg!("Consensus finalized for epoch {} in subnet {}", epoch, subnet_id);
+        msg!("Finalization of epoch {} in subnet {}: {:?}", 
+             epoch, subnet_id, consensus_state.finalize_stage);
 
         Ok(())
     }
 
-    /// Compute weighted median consensus for miners
-    fn compute_weighted_median(
-        submissions: &[WeightSubmission],
-        stake_accounts: &[AccountInfo],
-    ) -> Result<Vec<ConsensusEntry>> {
-        use std::collections::HashMap;
-
-        // Aggregate weights by miner UID
-        let mut miner_weights: HashMap<u16, Vec<(u64, u64)>> = HashMap::new(); // (weight, stake)
-
-        for submission in submissions {
-            // Get validator stake weight
-            let stake_weight = Self::get_validator_stake_weight(
-                submission.validator_uid,
-                stake_accounts,
-            )?;
-
-            for entry in &submission.weights {
-                miner_weights
-                    .entry(entry.miner_uid)
-                    .or_insert_with(Vec::new)
-                    .push((entry.weight, stake_weight));
-            }
-        }
+    /// Close a validator's submission once its epoch is finalized and
+    /// `CONSENSUS_RETENTION_EPOCHS` old, refunding the rent to the validator
+    /// (permissionless). Until then it names the hotkey slashing applies to.
+    pub fn close_weight_submission(
+        ctx: Context<CloseWeightSubmission>,
+        subnet_id: u16,
+        epoch: u64,
+        validator_uid: u16,
+    ) -> Result<()> {
+        require!(
+            epoch_settled(&ctx.accounts.consensus_state)?,
+            ConsensusError::EpochNotFinalized
+        );
+        require!(
+            ctx.accounts.submission.epoch + CONSENSUS_RETENTION_EPOCHS < epoch_at(Clock::get()?.unix_timestamp),
+            ConsensusError::ConsensusRetained
+        );
+
+        msg!("Closed submission of validator {} for epoch {} in subnet {}", 
+             validator_uid, epoch, subnet_id);
 
-        // Compute weighted median for each miner
-        let mut consensus_entries = Vec::new();
+        Ok(())
+    }
 
-        for (miner_uid, weights) in miner_weights {
-            // Sort by weight value
-            let mut sorted_weights = weights.clone();
-            sorted_weights.sort_by_key(|(w, _)| *w);
+    /// Slash a validator of a finalized epoch through the staking program
+    /// (permissionless): `UNREVEALED_SLASH_BPS` for committing without
+    /// revealing, `DEVIATION_SLASH_BPS` for trust below
+    /// `SLASH_TRUST_THRESHOLD`. Revealing no weights is an abstention and is
+    /// not slashed. A validator is slashed at most once per epoch.
+    pub fn slash_validator(
+        ctx: Context<SlashValidator>,
+        subnet_id: u16,
+        epoch: u64,
+        validator_uid: u16,
+    ) -> Result<()> {
+        let (slash_bps, reason) = {
+            let consensus_state = &mut ctx.accounts.consensus_state;
+            require!(consensus_state.finalized, ConsensusError::EpochNotFinalized);
 
-            // Calculate cumulative stake
-            let total_stake: u64 = sorted_weights.iter().map(|(_, s)| s).sum();
-            let median_stake = total_stake / 2;
+            let trust = consensus_state
+                .validator_consensus
+                .binary_search_by_key(&validator_uid, |e| e.uid)
+                .map(|i| consensus_state.validator_consensus[i].trust_score)
+                .map_err(|_| error!(ConsensusError::InvalidSubmission))?;
+            let validator = consensus_state
+                .validator_stakes
+                .iter_mut()
+                .find(|v| v.validator_uid == validator_uid)
+                .ok_or(ConsensusError::InvalidSubmission)?;
+            require!(!validator.slashed, ConsensusError::AlreadySlashed);
 
-            // Find weighted median
-            let mut cumulative_stake = 0u64;
-            let mut median_weight = 0u64;
+            let penalty = slash_penalty(validator, trust)?;
+            validator.slashed = true;
+            penalty
+        };
 
-            for (weight, stake) in &sorted_weights {
-                cumulative_stake += stake;
-                if cumulative_stake >= median_stake {
-                    median_weight = *weight;
-                    break;
-                }
-            }
+        let accounts = &ctx.accounts;
+        let cpi_accounts = poi_staking::cpi::accounts::Slash {
+            stake_account: accounts.stake_account.to_account_info(),
+            stake_config: accounts.stake_config.to_account_info(),
+            consensus_authority: accounts.staking_authority.to_account_info(),
+            treasury: accounts.treasury.to_account_info(),
+            alpha_vault: accounts.alpha_vault.as_ref().map(|a| a.to_account_info()),
+            treasury_alpha: accounts.treasury_alpha.as_ref().map(|a| a.to_account_info()),
+            token_program: accounts.token_program.as_ref().map(|a| a.to_account_info()),
+            validator_set: accounts.validator_set.as_ref().map(|a| a.to_account_info()),
+            neuron: accounts.neuron.to_account_info(),
+            registry_authority: accounts.registry_authority.to_account_info(),
+            registry_program: accounts.registry_program.to_account_info(),
+        };
 
-            // Apply clipping (remove outliers >2σ from median)
-            let clipped_weight = Self::clip_outliers(median_weight, &sorted_weights);
+        poi_staking::cpi::slash(
+            CpiContext::new_with_signer(
+                accounts.staking_program.to_account_info(),
+                cpi_accounts,
+                &[&[b"staking_authority", &[ctx.bumps.staking_authority]]],
+            ),
+            slash_bps,
+            reason,
+        )?;
 
-            consensus_entries.push(ConsensusEntry {
-                uid: miner_uid,
-                consensus_weight: clipped_weight,
-                trust_score: 0, // Calculated separately
-                emission_share: 0, // Calculated in emissions program
-            });
-        }
+        msg!("Slashed validator {} for epoch {} in subnet {}: {:?}", 
+             validator_uid, epoch, subnet_id, reason);
 
-        Ok(consensus_entries)
+        Ok(())
     }
 
-    /// Get validator stake weight (simplified - would need proper account deserialization)
-    fn get_validator_stake_weight(
-        _validator_uid: u16,
-        _stake_accounts: &[AccountInfo],
-    ) -> Result<u64> {
-        // TODO: Deserialize stake account and calculate weight
-        // For now, return placeholder
-        Ok(1000) // Placeholder
+    /// Close a miner page of a finalized epoch once it is
+    /// `CONSENSUS_RETENTION_EPOCHS` old, refunding its rent to whoever opened
+    /// it (permissionless)
+    pub fn close_miner_page(
+        ctx: Context<CloseMinerPage>,
+        subnet_id: u16,
+        epoch: u64,
+        page: u8,
+    ) -> Result<()> {
+        require!(
+            epoch_settled(&ctx.accounts.consensus_state)?,
+            ConsensusError::EpochNotFinalized
+        );
+        require!(
+            ctx.accounts.miner_page.epoch + CONSENSUS_RETENTION_EPOCHS < epoch_at(Clock::get()?.unix_timestamp),
+            ConsensusError::ConsensusRetained
+        );
+
+        msg!("Closed miner page {} for epoch {} in subnet {}", page, epoch, subnet_id);
+
+        Ok(())
     }
 
-    /// Clip outliers using 2-sigma rule
-    fn clip_outliers(median: u64, weights: &[(u64, u64)]) -> u64 {
-        if weights.is_empty() {
-            return median;
-        }
+    /// Close a finalized epoch header once it is `CONSENSUS_RETENTION_EPOCHS`
+    /// old, refunding its rent to the first committer (permissionless)
+    pub fn close_consensus_state(
+        ctx: Context<CloseConsensusState>,
+        subnet_id: u16,
+        epoch: u64,
+    ) -> Result<()> {
+        let consensus_state = &ctx.accounts.consensus_state;
+        require!(consensus_state.finalized, ConsensusError::EpochNotFinalized);
+        require!(
+            consensus_state.epoch + CONSENSUS_RETENTION_EPOCHS < epoch_at(Clock::get()?.unix_timestamp),
+            ConsensusError::ConsensusRetained
+        );
 
-        // Calculate standard deviation
-        let mean: f64 = weights.iter()
-            .map(|(w, _)| *w as f64)
-            .sum::<f64>() / weights.len() as f64;
+        msg!("Closed consensus for epoch {} in subnet {}", epoch, subnet_id);
 
-        let variance: f64 = weights.iter()
-            .map(|(w, _)| {
-                let diff = *w as f64 - mean;
-                diff * diff
-            })
-            .sum::<f64>() / weights.len() as f64;
+        Ok(())
+    }
 
-        let std_dev = variance.sqrt();
-        let threshold = 2.0 * std_dev;
+    /// Close a UID's bonds once their hotkey no longer holds a validator
+    /// permit at that UID, refunding the rent to whoever created them
+    /// (permissionless). `neuron` is the registry Neuron of the bonds' hotkey.
+    pub fn close_validator_bonds(
+        ctx: Context<CloseValidatorBonds>,
+        subnet_id: u16,
+        validator_uid: u16,
+    ) -> Result<()> {
+        // A pruned or re-registered hotkey fails to read as this UID
+        let still_validator = read_neuron(&ctx.accounts.neuron)
+            .map(|neuron| neuron.uid == validator_uid && neuron.is_validator)
+            .unwrap_or(false);
+        require!(!still_validator, ConsensusError::BondsInUse);
 
-        // Clip values outside 2σ
-        let clipped: Vec<u64> = weights.iter()
-            .map(|(w, _)| *w)
-            .filter(|w| {
-                let diff = (*w as f64 - mean).abs();
-                diff <= threshold
-            })
-            .collect();
+        msg!("Closed bonds of validator {} in subnet {}", validator_uid, subnet_id);
 
-        if clipped.is_empty() {
-            median
-        } else {
-            clipped.iter().sum::<u64>() / clipped.len() as u64
-        }
+        Ok(())
+    }
+}
+
+/// Whether an epoch header is finalized or already closed, which it only
+/// can be once finalized
+fn epoch_settled(consensus_state: &AccountInfo) -> Result<bool> {
+    if consensus_state.owner != &crate::ID || consensus_state.data_is_empty() {
+        return Ok(true);
     }
 
-    /// Calculate trust scores for miners and validators
-    fn calculate_trust_scores(
-        submissions: &[WeightSubmission],
-        miner_consensus: &[ConsensusEntry],
-    ) -> Result<TrustScores> {
-        use std::collections::HashMap;
+    let header = ConsensusState::try_deserialize(&mut consensus_state.try_borrow_data()?.as_ref())?;
+    Ok(header.finalized)
+}
+
+/// Whether `info` is an account of type `T` owned by this program
+fn is_account<T: Discriminator>(info: &AccountInfo) -> bool {
+    info.owner == &crate::ID
+        && info
+            .try_borrow_data()
+            .map(|data| data.starts_with(&T::DISCRIMINATOR))
+            .unwrap_or(false)
+}
+
+/// Deserialize a submission account, checking it belongs to the epoch
+fn read_submission(
+    consensus_state: &ConsensusState,
+    info: &AccountInfo,
+) -> Result<WeightSubmissionAccount> {
+    require_keys_eq!(*info.owner, crate::ID, ConsensusError::InvalidSubmission);
+
+    let submission = WeightSubmissionAccount::try_deserialize(&mut info.try_borrow_data()?.as_ref())?;
+    require!(
+        submission.subnet_id == consensus_state.subnet_id
+            && submission.epoch == consensus_state.epoch,
+        ConsensusError::InvalidSubmission
+    );
+
+    Ok(submission)
+}
+
+/// Deserialize a miner page, checking it belongs to the epoch
+fn read_miner_page(consensus_state: &ConsensusState, info: &AccountInfo) -> Result<MinerPage> {
+    require_keys_eq!(*info.owner, crate::ID, ConsensusError::InvalidMinerPage);
 
-        // Build consensus map
-        let consensus_map: HashMap<u16, u64> = miner_consensus
+    let page = MinerPage::try_deserialize(&mut info.try_borrow_data()?.as_ref())?;
+    require!(
+        page.subnet_id == consensus_state.subnet_id && page.epoch == consensus_state.epoch,
+        ConsensusError::InvalidMinerPage
+    );
+
+    Ok(page)
+}
+
+/// Page holding a miner UID's votes and consensus
+fn page_of(miner_uid: u16) -> u8 {
+    (miner_uid as usize / MINERS_PER_PAGE) as u8
+}
+
+/// Record stake for the submissions in a batch, each exactly once, and add
+/// the weights of revealed ones to the miner pages passed with them. Pages
+/// grow to fit their votes, paid for by `payer`.
+fn accumulate_submissions<'info>(
+    consensus_state: &mut ConsensusState,
+    accounts: &[AccountInfo<'info>],
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    // (validator slot, weights) of the revealed submissions in the batch
+    let mut batch: Vec<(usize, Vec<(u16, u64)>)> = Vec::new();
+
+    for info in accounts.iter().filter(|a| is_account::<WeightSubmissionAccount>(a)) {
+        let submission = read_submission(consensus_state, info)?;
+
+        let recorded = consensus_state
+            .validator_stakes
             .iter()
-            .map(|e| (e.uid, e.consensus_weight))
-            .collect();
-
-        // Calculate miner trust (alignment with consensus)
-        let mut miner_trust = Vec::new();
-        for entry in miner_consensus {
-            let consensus = entry.consensus_weight;
-            let mut alignment_sum = 0u64;
-            let mut count = 0u64;
-
-            for submission in submissions {
-                if let Some(weight_entry) = submission.weights.iter()
-                    .find(|w| w.miner_uid == entry.uid) {
-                    let diff = if weight_entry.weight > consensus {
-                        weight_entry.weight - consensus
-                    } else {
-                        consensus - weight_entry.weight
-                    };
-                    // Trust = inverse of deviation (normalized to 0-10000)
-                    let alignment = 10000u64.saturating_sub(diff.min(10000));
-                    alignment_sum += alignment;
-                    count += 1;
-                }
+            .position(|v| v.validator_uid == submission.validator_uid);
+        let slot = match recorded {
+            Some(slot) => slot,
+            None => {
+                // Unrevealed commits carry no weights, only the trust penalty later
+                let stake_weight = if submission.revealed {
+                    submission.stake_weight
+                } else {
+                    0
+                };
+                // Abstentions cast no votes
+                let pages = if abstains(&submission.weights) {
+                    0
+                } else {
+                    submission
+                        .weights
+                        .iter()
+                        .fold(0u16, |pages, e| pages | 1 << page_of(e.miner_uid))
+                };
+                consensus_state.miner_pages |= pages;
+
+                consensus_state.validator_stakes.push(ValidatorStake {
+                    validator_uid: submission.validator_uid,
+                    stake_weight,
+                    revealed: submission.revealed,
+                    pages,
+                    absorbed_pages: 0,
+                    slashed: false,
+                });
+                consensus_state.validator_stakes.len() - 1
             }
+        };
 
-            let trust = if count > 0 {
-                alignment_sum / count
-            } else {
-                0
-            };
+        if submission.revealed {
+            batch.push((slot, normalized_weights(&submission)));
+        }
+    }
 
-            miner_trust.push((entry.uid, trust));
-        }
-
-        // Calculate validator trust (alignment with final consensus)
-        let mut validator_trust = Vec::new();
-        for submission in submissions {
-            let mut alignment_sum = 0u64;
-            let mut count = 0u64;
-
-            for weight_entry in &submission.weights {
-                if let Some(&consensus_weight) = consensus_map.get(&weight_entry.miner_uid) {
-                    let diff = if weight_entry.weight > consensus_weight {
-                        weight_entry.weight - consensus_weight
-                    } else {
-                        consensus_weight - weight_entry.weight
-                    };
-                    let alignment = 10000u64.saturating_sub(diff.min(10000));
-                    alignment_sum += alignment;
-                    count += 1;
-                }
+    for info in accounts.iter().filter(|a| is_account::<MinerPage>(a)) {
+        let mut page = read_miner_page(consensus_state, info)?;
+        let bit = 1u16 << page.page;
+        let votes = page.votes.len();
+
+        for (slot, weights) in &batch {
+            let validator = &mut consensus_state.validator_stakes[*slot];
+            if validator.pages & bit == 0 || validator.absorbed_pages & bit != 0 {
+                continue;
+            }
+            validator.absorbed_pages |= bit;
+
+            for (miner_uid, weight) in weights.iter().filter(|(uid, _)| page_of(*uid) == page.page) {
+                page.votes.push(MinerVote {
+                    miner_uid: *miner_uid,
+                    validator_slot: *slot as u8,
+                    weight: *weight,
+                });
+            }
+        }
+
+        if page.votes.len() > votes {
+            grow_account(info, payer, system_program, 8 + MinerPage::space(page.votes.len()))?;
+            page.try_serialize(&mut info.try_borrow_mut_data()?.as_mut())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Grow an account owned by this program to `len` bytes, topping its rent up
+/// from `payer`
+fn grow_account<'info>(
+    info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    len: usize,
+) -> Result<()> {
+    if info.data_len() >= len {
+        return Ok(());
+    }
+
+    let top_up = Rent::get()?.minimum_balance(len).saturating_sub(info.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: info.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    info.realloc(len, false)?;
+
+    Ok(())
+}
+
+/// Compute consensus for the miners of each page passed from the votes
+/// collected in it. The votes are dropped once the page is computed.
+fn compute_page_consensus(
+    consensus_state: &mut ConsensusState,
+    accounts: &[AccountInfo],
+) -> Result<()> {
+    let yuma = consensus_state.mode == ConsensusMode::Yuma;
+    let (total_stake, equal_stake) = revealed_stake(consensus_state);
+    // Reused across miners; it never holds more than one vote per validator
+    let mut weights: Vec<(u64, u64)> = Vec::with_capacity(MAX_SUBMISSIONS);
+
+    for info in accounts {
+        let mut page = read_miner_page(consensus_state, info)?;
+        let bit = 1u16 << page.page;
+        require!(
+            consensus_state.miner_pages & bit != 0 && consensus_state.computed_pages & bit == 0,
+            ConsensusError::InvalidMinerPage
+        );
+
+        // Votes arrive in submission order; group them by miner in UID order
+        page.votes.sort_unstable_by_key(|v| (v.miner_uid, v.validator_slot));
+
+        let mut start = 0;
+        while start < page.votes.len() {
+            let miner_uid = page.votes[start].miner_uid;
+            let end = start + page.votes[start..].iter().take_while(|v| v.miner_uid == miner_uid).count();
+
+            weights.clear();
+            for vote in &page.votes[start..end] {
+                let stake_weight = consensus_state.validator_stakes[vote.validator_slot as usize].stake_weight;
+                let stake = if yuma && equal_stake { 1 } else { stake_weight };
+                weights.push((vote.weight, stake));
             }
 
-            let trust = if count > 0 {
-                alignment_sum / count
+            let entry = if yuma {
+                yuma_miner_entry(miner_uid, &mut weights, total_stake, consensus_state.kappa_bps)
             } else {
-                0
+                miner_consensus_entry(miner_uid, &mut weights)
             };
+            consensus_state.total_rank += entry.rank;
+            page.miner_consensus.push(entry);
+
+            start = end;
+        }
+
+        page.votes.clear();
+        page.try_serialize(&mut info.try_borrow_mut_data()?.as_mut())?;
+        consensus_state.computed_pages |= bit;
+    }
+
+    Ok(())
+}
+
+/// Score a batch of validators against the final consensus. Committing
+/// without revealing forfeits all validator trust.
+fn compute_trust_batch(
+    consensus_state: &mut ConsensusState,
+    accounts: &[AccountInfo],
+) -> Result<()> {
+    let yuma = consensus_state.mode == ConsensusMode::Yuma;
+
+    let mut pages: Vec<MinerPage> = Vec::new();
+    for info in accounts.iter().filter(|a| is_account::<MinerPage>(a)) {
+        let mut page = read_miner_page(consensus_state, info)?;
+        require!(
+            consensus_state.computed_pages & (1 << page.page) != 0,
+            ConsensusError::InvalidMinerPage
+        );
+
+        if yuma {
+            // Incentive is each miner's share of the total rank, known
+            // once every page is computed
+            for entry in page.miner_consensus.iter_mut() {
+                entry.incentive = fraction(entry.rank as u128, consensus_state.total_rank as u128);
+            }
+            page.try_serialize(&mut info.try_borrow_mut_data()?.as_mut())?;
+        }
+        pages.push(page);
+    }
 
-            validator_trust.push(ConsensusEntry {
+    let others: Vec<&AccountInfo> = accounts.iter().filter(|a| !is_account::<MinerPage>(a)).collect();
+    let accounts_per_submission = if yuma { 2 } else { 1 };
+    require!(others.len() % accounts_per_submission == 0, ConsensusError::MissingBonds);
+
+    for group in others.chunks(accounts_per_submission) {
+        let submission = read_submission(consensus_state, group[0])?;
+
+        let entry = if !submission.revealed || abstains(&submission.weights) {
+            if submission.revealed {
+                msg!("Validator {} abstained", submission.validator_uid);
+            } else {
+                msg!("Validator {} did not reveal its weights", submission.validator_uid);
+            }
+            if yuma {
+                // Bonds decay as if the validator had weighted nothing
+                if let Some(mut bonds) = load_bonds(group[1], &submission)? {
+                    update_bonds(&mut bonds, &[], consensus_state);
+                    bonds.try_serialize(&mut group[1].try_borrow_mut_data()?.as_mut())?;
+                }
+            }
+            ConsensusEntry {
                 uid: submission.validator_uid,
                 consensus_weight: 0,
-                trust_score: trust,
+                trust_score: 0,
                 emission_share: 0,
-            });
+                rank: 0,
+                incentive: 0,
+                dividends: 0,
+            }
+        } else if yuma {
+            let bonds_info = group[1];
+            let mut bonds = load_bonds(bonds_info, &submission)?;
+
+            let entry = yuma_validator_entry(consensus_state, &pages, &submission, bonds.as_mut())?;
+            if let Some(bonds) = bonds {
+                bonds.try_serialize(&mut bonds_info.try_borrow_mut_data()?.as_mut())?;
+            }
+            entry
+        } else {
+            validator_trust_entry(consensus_state, &pages, &submission)?
+        };
+        insert_validator_entry(&mut consensus_state.validator_consensus, entry)?;
+    }
+
+    Ok(())
+}
+
+/// A miner's consensus, read from its page among `pages`. `None` if no
+/// revealed validator weighted the miner.
+fn miner_result<'a>(
+    consensus_state: &ConsensusState,
+    pages: &'a [MinerPage],
+    miner_uid: u16,
+) -> Result<Option<&'a ConsensusEntry>> {
+    let page_index = page_of(miner_uid);
+    if (miner_uid as usize) >= MAX_CONSENSUS_MINERS || consensus_state.miner_pages & (1 << page_index) == 0 {
+        return Ok(None);
+    }
+
+    let page = pages
+        .iter()
+        .find(|p| p.page == page_index)
+        .ok_or(ConsensusError::MissingMinerPage)?;
+    Ok(page
+        .miner_consensus
+        .binary_search_by_key(&miner_uid, |e| e.uid)
+        .ok()
+        .map(|i| &page.miner_consensus[i]))
+}
+
+/// Load the validator bonds passed for `submission`. `None` if the account
+/// was closed or the UID's bonds now belong to another hotkey.
+fn load_bonds(info: &AccountInfo, submission: &WeightSubmissionAccount) -> Result<Option<ValidatorBonds>> {
+    let (bonds_pda, _bump) = Pubkey::find_program_address(
+        &[
+            b"bonds",
+            submission.subnet_id.to_le_bytes().as_ref(),
+            submission.validator_uid.to_le_bytes().as_ref(),
+        ],
+        &crate::ID,
+    );
+    require_keys_eq!(info.key(), bonds_pda, ConsensusError::MissingBonds);
+
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(None);
+    }
+    let bonds = ValidatorBonds::try_deserialize(&mut info.try_borrow_data()?.as_ref())?;
+
+    Ok(Some(bonds).filter(|b| b.validator == submission.validator))
+}
+
+/// Yuma trust, bonds and dividends for one revealed validator. Bonds are an
+/// EMA of the validator's share of each miner's clipped support, so
+/// validators that back a miner before the rest of the subnet hold a larger
+/// share of it; dividends are bonds × incentive. A validator without bonds
+/// earns no dividends.
+fn yuma_validator_entry(
+    consensus_state: &ConsensusState,
+    pages: &[MinerPage],
+    submission: &WeightSubmissionAccount,
+    bonds: Option<&mut ValidatorBonds>,
+) -> Result<ConsensusEntry> {
+    let stake_weight = consensus_state
+        .validator_stakes
+        .iter()
+        .find(|v| v.validator_uid == submission.validator_uid)
+        .map(|v| v.stake_weight)
+        .ok_or(ConsensusError::InvalidSubmission)?;
+    let (total_stake, equal_stake) = revealed_stake(consensus_state);
+    let stake = if equal_stake { 1 } else { stake_weight as u128 };
+
+    let mut kept = 0u128;
+    let mut submitted = 0u128;
+    let mut epoch_bonds: Vec<BondEntry> = Vec::new();
+
+    for (miner_uid, weight) in normalized_weights(submission) {
+        let miner = match miner_result(consensus_state, pages, miner_uid)? {
+            Some(miner) => miner,
+            None => continue,
+        };
+
+        let clipped = weight.min(miner.consensus_weight);
+        kept += clipped as u128;
+        submitted += weight as u128;
+
+        // Share of the miner's rank contributed by this validator
+        let bond = fraction(stake * clipped as u128, miner.rank as u128 * total_stake).min(CONSENSUS_PRECISION);
+        epoch_bonds.push(BondEntry { miner_uid, bond });
+    }
+
+    let mut dividends = 0u128;
+    if let Some(bonds) = bonds {
+        update_bonds(bonds, &epoch_bonds, consensus_state);
+
+        for bond in &bonds.bonds {
+            if let Some(miner) = miner_result(consensus_state, pages, bond.miner_uid)? {
+                dividends += bond.bond as u128 * miner.incentive as u128;
+            }
+        }
+    }
+
+    Ok(ConsensusEntry {
+        uid: submission.validator_uid,
+        consensus_weight: 0,
+        trust_score: fraction(kept, submitted),
+        emission_share: 0,
+        rank: 0,
+        incentive: 0,
+        dividends: (dividends / CONSENSUS_PRECISION as u128) as u64,
+    })
+}
+
+/// Bring a validator's bonds up to the finalized epoch: each epoch since the
+/// last update that they were not updated for (no commit, or a later epoch
+/// finalized first) decays them by `(1 - alpha)`, then the epoch mixes in
+/// `epoch_bonds`, empty if the validator did not reveal. An epoch finalized
+/// after a later one leaves the bonds as they are.
+fn update_bonds(bonds: &mut ValidatorBonds, epoch_bonds: &[BondEntry], consensus_state: &ConsensusState) {
+    if bonds.last_epoch >= consensus_state.epoch {
+        return;
+    }
+
+    let missed = consensus_state.epoch - bonds.last_epoch - 1;
+    let retention = bond_retention(consensus_state.bond_alpha_bps, missed) as u128;
+    for bond in bonds.bonds.iter_mut() {
+        bond.bond = (bond.bond as u128 * retention / CONSENSUS_PRECISION as u128) as u64;
+    }
+
+    bonds.bonds = bond_moving_average(&bonds.bonds, epoch_bonds, consensus_state.bond_alpha_bps);
+    bonds.last_epoch = consensus_state.epoch;
+}
+
+/// `(1 - alpha)^epochs` as a fraction of `CONSENSUS_PRECISION`, by squaring
+fn bond_retention(alpha_bps: u64, epochs: u64) -> u64 {
+    let precision = CONSENSUS_PRECISION as u128;
+    let mut base = (BPS_DENOMINATOR - alpha_bps) as u128 * precision / BPS_DENOMINATOR as u128;
+    let mut retention = precision;
+    let mut exponent = epochs;
+
+    while exponent > 0 && retention > 0 {
+        if exponent & 1 == 1 {
+            retention = retention * base / precision;
+        }
+        base = base * base / precision;
+        exponent >>= 1;
+    }
+
+    retention as u64
+}
+
+/// `alpha · current + (1 - alpha) · previous` per miner, both sorted by UID.
+/// Bonds that decay to zero are dropped.
+fn bond_moving_average(previous: &[BondEntry], current: &[BondEntry], alpha_bps: u64) -> Vec<BondEntry> {
+    let mut merged: Vec<BondEntry> = Vec::with_capacity(previous.len() + current.len());
+    let (mut i, mut j) = (0, 0);
+
+    while i < previous.len() || j < current.len() {
+        let prev_uid = previous.get(i).map(|b| b.miner_uid).unwrap_or(u16::MAX);
+        let curr_uid = current.get(j).map(|b| b.miner_uid).unwrap_or(u16::MAX);
+        let miner_uid = prev_uid.min(curr_uid);
+
+        let mut old = 0u128;
+        let mut new = 0u128;
+        if i < previous.len() && prev_uid == miner_uid {
+            old = previous[i].bond as u128;
+            i += 1;
+        }
+        if j < current.len() && curr_uid == miner_uid {
+            new = current[j].bond as u128;
+            j += 1;
+        }
+
+        let bond = ((alpha_bps as u128 * new + (BPS_DENOMINATOR - alpha_bps) as u128 * old)
+            / BPS_DENOMINATOR as u128) as u64;
+        if bond > 0 {
+            merged.push(BondEntry { miner_uid, bond });
         }
+    }
+
+    merged
+}
+
+/// Load a subnet's consensus config, falling back to defaults if it was
+/// never created
+fn load_consensus_config(consensus_config: &AccountInfo) -> Result<ConsensusConfig> {
+    if consensus_config.owner != &crate::ID || consensus_config.data_is_empty() {
+        return Ok(ConsensusConfig::default());
+    }
 
-        Ok(TrustScores {
-            miner_trust,
-            validator_trust,
-        })
+    let data = consensus_config.try_borrow_data()?;
+    ConsensusConfig::try_deserialize(&mut data.as_ref())
+}
+
+/// Insert a validator entry in UID order, rejecting duplicates
+fn insert_validator_entry(entries: &mut Vec<ConsensusEntry>, entry: ConsensusEntry) -> Result<()> {
+    match entries.binary_search_by_key(&entry.uid, |e| e.uid) {
+        Ok(_) => err!(ConsensusError::InvalidSubmission),
+        Err(i) => {
+            entries.insert(i, entry);
+            Ok(())
+        }
     }
 }
 
+/// Where an epoch is in its commit-reveal schedule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EpochPhase {
+    NotStarted,
+    Commit,
+    Reveal,
+    Closed,
+}
+
+/// Epochs follow the staking schedule: commits are open for the first
+/// `COMMIT_PHASE_DURATION` seconds, reveals for the rest of the epoch
+fn epoch_phase(epoch: u64, now: i64) -> EpochPhase {
+    let start = epoch as i64 * EPOCH_DURATION;
+    if now < start {
+        EpochPhase::NotStarted
+    } else if now < start + COMMIT_PHASE_DURATION {
+        EpochPhase::Commit
+    } else if now < start + EPOCH_DURATION {
+        EpochPhase::Reveal
+    } else {
+        EpochPhase::Closed
+    }
+}
+
+/// Commitment to a weight vector: sha256 over the validator's hotkey,
+/// `subnet_id` (u16 LE) and `epoch` (u64 LE), then each entry's `miner_uid`
+/// (u16 LE) and `weight` (u64 LE) in order, then the salt. Binding the
+/// hotkey and epoch stops a commitment being replayed by another validator
+/// or in a later epoch.
+pub fn weights_commitment(
+    validator: &Pubkey,
+    subnet_id: u16,
+    epoch: u64,
+    weights: &[WeightEntry],
+    salt: &[u8; 32],
+) -> [u8; 32] {
+    let mut preimage = Vec::with_capacity(32 + 2 + 8 + weights.len() * WeightEntry::LEN + 32);
+    preimage.extend_from_slice(validator.as_ref());
+    preimage.extend_from_slice(&subnet_id.to_le_bytes());
+    preimage.extend_from_slice(&epoch.to_le_bytes());
+    for entry in weights {
+        preimage.extend_from_slice(&entry.miner_uid.to_le_bytes());
+        preimage.extend_from_slice(&entry.weight.to_le_bytes());
+    }
+    preimage.extend_from_slice(salt);
+
+    hash(&preimage).to_bytes()
+}
+
+/// Check the signer is the registered hotkey of `validator_uid` with a permit
+fn verify_validator(
+    neuron: &AccountInfo,
+    signer: &Pubkey,
+    subnet_id: u16,
+    validator_uid: u16,
+) -> Result<()> {
+    let validator = read_neuron(neuron)?;
+    require!(
+        validator.uid == validator_uid
+            && validator.uid != 0
+            && validator.subnet_id == subnet_id
+            && validator.hotkey == *signer,
+        ConsensusError::InvalidNeuron
+    );
+    require!(validator.is_validator, ConsensusError::NotValidator);
+
+    Ok(())
+}
+
+/// Fields consensus needs from a registry Neuron account
+struct RegistryNeuron {
+    uid: u16,
+    subnet_id: u16,
+    hotkey: Pubkey,
+    is_validator: bool,
+}
+
+/// Read a registry Neuron, checking it is owned by the registry program
+fn read_neuron(neuron: &AccountInfo) -> Result<RegistryNeuron> {
+    require_keys_eq!(*neuron.owner, REGISTRY_PROGRAM_ID, ConsensusError::InvalidNeuron);
+
+    let data = neuron.try_borrow_data()?;
+    // Subnet and network accounts are shorter than a Neuron
+    require!(data.len() >= 125, ConsensusError::InvalidNeuron);
+
+    // Registry Neuron layout: uid (2) | subnet_id (2) | hotkey (32) | coldkey (32) |
+    // stake, rank, trust, incentive, validator_trust (8 each) | is_validator (1) | ...
+    Ok(RegistryNeuron {
+        uid: u16::from_le_bytes([data[0], data[1]]),
+        subnet_id: u16::from_le_bytes([data[2], data[3]]),
+        hotkey: Pubkey::try_from(&data[4..36]).map_err(|_| error!(ConsensusError::InvalidNeuron))?,
+        is_validator: data[108] != 0,
+    })
+}
+
+/// Weighted median consensus and trust for one miner from (weight, stake)
+/// pairs, which are sorted in place
+fn miner_consensus_entry(miner_uid: u16, weights: &mut [(u64, u64)]) -> ConsensusEntry {
+    // Canonical order, independent of the order submissions were passed in
+    weights.sort_unstable();
+    let sorted_weights: &[(u64, u64)] = weights;
+
+    let median_weight = weighted_median(sorted_weights);
+
+    // Stake-weighted mean of the weights within 2σ
+    let clipped_weight = clip_outliers(median_weight, sorted_weights);
+
+    // Miner trust = alignment of the submitted weights with consensus
+    let alignment_sum: u64 = sorted_weights
+        .iter()
+        .map(|(weight, _)| alignment(*weight, clipped_weight))
+        .sum();
+
+    let trust = if sorted_weights.is_empty() {
+        0
+    } else {
+        alignment_sum / sorted_weights.len() as u64
+    };
+
+    ConsensusEntry {
+        uid: miner_uid,
+        consensus_weight: clipped_weight,
+        trust_score: trust,
+        emission_share: 0, // Calculated in emissions program
+        rank: 0,
+        incentive: 0,
+        dividends: 0,
+    }
+}
+
+/// Yuma consensus for one miner from the (normalized weight, stake) of the
+/// validators that weighted it. Consensus is the highest weight backed by at
+/// least κ of the revealed stake, where validators that left the miner out
+/// back only zero. Weights above consensus are clipped: rank is the
+/// stake-weighted clipped weight and trust the share of weight kept.
+fn yuma_miner_entry(miner_uid: u16, weights: &mut [(u64, u64)], total_stake: u128, kappa_bps: u64) -> ConsensusEntry {
+    // Highest weights first; equal weights are interchangeable
+    weights.sort_unstable_by(|a, b| b.cmp(a));
+    let sorted_weights: &[(u64, u64)] = weights;
+
+    let mut support = 0u128;
+    let mut consensus = 0u64;
+    for (weight, stake) in sorted_weights {
+        support += *stake as u128;
+        if support * BPS_DENOMINATOR as u128 >= total_stake * kappa_bps as u128 {
+            consensus = *weight;
+            break;
+        }
+    }
+
+    let mut clipped_support = 0u128;
+    let mut support = 0u128;
+    for (weight, stake) in sorted_weights {
+        clipped_support += *stake as u128 * (*weight).min(consensus) as u128;
+        support += *stake as u128 * *weight as u128;
+    }
+
+    ConsensusEntry {
+        uid: miner_uid,
+        consensus_weight: consensus,
+        trust_score: fraction(clipped_support, support),
+        emission_share: 0,
+        rank: (clipped_support / total_stake.max(1)) as u64,
+        incentive: 0, // Set once every miner is ranked
+        dividends: 0,
+    }
+}
+
+/// Whether revealed weights put nothing on any miner, which abstains from
+/// the epoch rather than voting every miner down
+fn abstains(weights: &[WeightEntry]) -> bool {
+    weights.iter().all(|e| e.weight == 0)
+}
+
+/// Slash for a submitting validator with `trust`: unrevealed commits and
+/// trust below `SLASH_TRUST_THRESHOLD` are slashable, abstentions are not
+fn slash_penalty(validator: &ValidatorStake, trust: u64) -> Result<(u16, SlashReason)> {
+    if !validator.revealed {
+        Ok((UNREVEALED_SLASH_BPS, SlashReason::InvalidWeights))
+    } else if validator.pages != 0 && trust < SLASH_TRUST_THRESHOLD {
+        Ok((DEVIATION_SLASH_BPS, SlashReason::ConsensusDeviation))
+    } else {
+        err!(ConsensusError::NotSlashable)
+    }
+}
+
+/// A submission's weights as fractions of their sum, in `CONSENSUS_PRECISION`
+fn normalized_weights(submission: &WeightSubmissionAccount) -> Vec<(u16, u64)> {
+    let total: u128 = submission.weights.iter().map(|e| e.weight as u128).sum();
+    submission
+        .weights
+        .iter()
+        .map(|e| (e.miner_uid, fraction(e.weight as u128, total)))
+        .collect()
+}
+
+/// Total stake behind revealed submissions. If none of them has stake, every
+/// revealed validator counts as one unit instead, flagged by the `bool`.
+fn revealed_stake(consensus_state: &ConsensusState) -> (u128, bool) {
+    let total: u128 = consensus_state
+        .validator_stakes
+        .iter()
+        .filter(|v| v.revealed)
+        .map(|v| v.stake_weight as u128)
+        .sum();
+
+    if total == 0 {
+        (consensus_state.revealed_count as u128, true)
+    } else {
+        (total, false)
+    }
+}
+
+/// `value / total` as a fraction of `CONSENSUS_PRECISION`, zero if `total`
+/// is zero and saturating at `u64::MAX`. The remainder is scaled without
+/// overflowing for any u128 inputs, rounding down.
+fn fraction(value: u128, total: u128) -> u64 {
+    if total == 0 {
+        return 0;
+    }
+
+    let precision = CONSENSUS_PRECISION as u128;
+    let whole = (value / total).saturating_mul(precision);
+
+    // `rest < total`; dropping low bits of both until `total` fits 98 bits
+    // keeps `rest · CONSENSUS_PRECISION` (< 2^128) from overflowing
+    let rest = value % total;
+    let shift = (128 - total.leading_zeros()).saturating_sub(98);
+    let part = (rest >> shift) * precision / (total >> shift);
+
+    u64::try_from(whole.saturating_add(part)).unwrap_or(u64::MAX)
+}
+
+/// Load the epoch's stake snapshot, if any stake changed during the epoch
+fn load_stake_snapshot(stake_snapshot: &AccountInfo) -> Result<Option<StakeSnapshot>> {
+    if stake_snapshot.owner != &poi_staking::ID || stake_snapshot.data_is_empty() {
+        return Ok(None);
+    }
+
+    let data = stake_snapshot.try_borrow_data()?;
+    StakeSnapshot::try_deserialize(&mut data.as_ref()).map(Some)
+}
+
+/// Get validator stake weight as of the start of the current epoch, while
+/// the epoch is still running. Validators missing from the snapshot have not
+/// changed their stake since the epoch started, so their live `StakeAccount`
+/// holds it. A full snapshot may have skipped the validator, which is an error.
+fn get_validator_stake_weight(
+    validator: &Pubkey,
+    config: &SubnetStakeConfig,
+    snapshot: Option<&StakeSnapshot>,
+    stake_info: &AccountInfo,
+) -> Result<u64> {
+    if let Some(snapshot) = snapshot {
+        if let Some(weight) = snapshot.weight_of(validator) {
+            return Ok(weight);
+        }
+        require!(
+            snapshot.entries.len() < MAX_SNAPSHOT_VALIDATORS,
+            ConsensusError::StakeSnapshotFull
+        );
+    }
+
+    require!(!stake_info.data_is_empty(), ConsensusError::MissingStakeAccount);
+    require_keys_eq!(
+        *stake_info.owner,
+        poi_staking::ID,
+        ConsensusError::InvalidStakeAccount
+    );
+
+    let stake_account = StakeAccount::try_deserialize(&mut stake_info.try_borrow_data()?.as_ref())?;
+    poi_staking::calculate_stake_weight(&stake_account, config)
+}
+
+/// Stake-weighted mean of the (weight, stake) pairs within 2σ of `median`,
+/// where σ² is the stake-weighted mean squared deviation from the median.
+/// Falls back to `median` if nothing within the band carries stake.
+fn clip_outliers(median: u64, weights: &[(u64, u64)]) -> u64 {
+    let shares = || stake_shares(weights);
+    let total_share: u128 = shares().map(|(_, s)| s).sum();
+    if total_share == 0 {
+        return median;
+    }
+
+    // Squared deviations saturate instead of overflowing, which at worst
+    // widens the band and clips nothing
+    let squared_deviation = |w: u64| -> u128 {
+        let diff = w.abs_diff(median) as u128;
+        diff.saturating_mul(diff)
+    };
+    let variance = shares()
+        .map(|(w, s)| squared_deviation(w).saturating_mul(s))
+        .fold(0u128, |acc, d| acc.saturating_add(d))
+        / total_share;
+
+    // |w - median| <= 2σ  <=>  (w - median)² <= 4σ²
+    let threshold = variance.saturating_mul(4);
+
+    // Shares total at most 2^32 and weights are below 2^64, so these fit a u128
+    let (kept_sum, kept_share) = shares()
+        .filter(|(w, _)| squared_deviation(*w) <= threshold)
+        .fold((0u128, 0u128), |(sum, share), (w, s)| (sum + w as u128 * s, share + s));
+
+    if kept_share == 0 {
+        median
+    } else {
+        // A weighted mean of u64 values always fits in a u64
+        (kept_sum / kept_share) as u64
+    }
+}
+
+/// Each pair's stake as a share of 2^32 of the total, so weighted sums of
+/// u64 weights cannot overflow a u128. With no stake at all, every pair
+/// counts as one share.
+fn stake_shares(weights: &[(u64, u64)]) -> impl Iterator<Item = (u64, u128)> + '_ {
+    let total_stake: u128 = weights.iter().map(|(_, s)| *s as u128).sum();
+
+    weights.iter().map(move |(w, s)| {
+        let share = if total_stake == 0 {
+            1
+        } else {
+            (*s as u128) * (1u128 << 32) / total_stake
+        };
+        (*w, share)
+    })
+}
+
+/// Stake-weighted lower median of (weight, stake) pairs sorted by weight:
+/// the smallest weight at which cumulative stake reaches half the total.
+/// Ties at exactly half resolve to the lower weight. With no stake behind
+/// any weight, every validator counts equally.
+fn weighted_median(sorted_weights: &[(u64, u64)]) -> u64 {
+    let total_stake: u128 = sorted_weights.iter().map(|(_, s)| *s as u128).sum();
+
+    let mut cumulative = 0u128;
+    for (weight, stake) in sorted_weights {
+        cumulative += if total_stake == 0 { 1 } else { *stake as u128 };
+        let total = if total_stake == 0 { sorted_weights.len() as u128 } else { total_stake };
+        if cumulative * 2 >= total {
+            return *weight;
+        }
+    }
+
+    0
+}
+
+/// How close a normalized weight is to consensus: `CONSENSUS_PRECISION`
+/// minus the distance between them
+fn alignment(weight: u64, consensus: u64) -> u64 {
+    CONSENSUS_PRECISION.saturating_sub(weight.abs_diff(consensus))
+}
+
+/// Validator trust: alignment of its normalized weights with the final consensus
+fn validator_trust_entry(
+    consensus_state: &ConsensusState,
+    pages: &[MinerPage],
+    submission: &WeightSubmissionAccount,
+) -> Result<ConsensusEntry> {
+    let mut alignment_sum = 0u64;
+    let mut count = 0u64;
+
+    for (miner_uid, weight) in normalized_weights(submission) {
+        if let Some(consensus) = miner_result(consensus_state, pages, miner_uid)? {
+            alignment_sum += alignment(weight, consensus.consensus_weight);
+            count += 1;
+        }
+    }
+
+    let trust = if count > 0 {
+        alignment_sum / count
+    } else {
+        0
+    };
+
+    Ok(ConsensusEntry {
+        uid: submission.validator_uid,
+        consensus_weight: 0,
+        trust_score: trust,
+        emission_share: 0,
+        rank: 0,
+        incentive: 0,
+        dividends: 0,
+    })
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16)]
+pub struct ConfigureConsensus<'info> {
+    #[account(
+        init_if_needed,
+        payer = governor,
+        space = 8 + ConsensusConfig::LEN,
+        seeds = [b"consensus_config", subnet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub consensus_config: Account<'info, ConsensusConfig>,
+    
+    /// CHECK: Registry subnet account, read for its governor
+    #[account(
+        seeds = [b"subnet", subnet_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID,
+        owner = REGISTRY_PROGRAM_ID
+    )]
+    pub subnet: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub governor: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
-#[instruction(subnet_id: u16, epoch: u64)]
-pub struct SubmitWeights<'info> {
+#[instruction(subnet_id: u16, epoch: u64, validator_uid: u16)]
+pub struct CommitWeights<'info> {
     #[account(
         init_if_needed,
         payer = validator_signer,
         space = 8 + ConsensusState::LEN,
-        seeds = [b"consensus", &subnet_id.to_le_bytes(), &epoch.to_le_bytes()],
+        seeds = [b"consensus", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
         bump
     )]
     pub consensus_state: Account<'info, ConsensusState>,
     
+    #[account(
+        init_if_needed,
+        payer = validator_signer,
+        space = 8 + WeightSubmissionAccount::LEN,
+        seeds = [b"weights", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref(), validator_uid.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub submission: Account<'info, WeightSubmissionAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = validator_signer,
+        space = 8 + ValidatorBonds::LEN,
+        seeds = [b"bonds", subnet_id.to_le_bytes().as_ref(), validator_uid.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub bonds: Account<'info, ValidatorBonds>,
+    
     #[account(mut)]
     pub validator_signer: Signer<'info>,
     
+    /// CHECK: Registry neuron for the signing hotkey, owned by the registry program
+    #[account(
+        seeds = [b"neuron", subnet_id.to_le_bytes().as_ref(), validator_signer.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: The signing hotkey's stake account, read if the snapshot lacks it
+    #[account(
+        seeds = [b"stake", validator_signer.key().as_ref(), subnet_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = poi_staking::ID
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(
+        seeds = [b"stake_config", subnet_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = poi_staking::ID
+    )]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: The epoch's stake snapshot PDA, absent if no stake changed yet
+    #[account(
+        seeds = [b"stake_snapshot", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = poi_staking::ID
+    )]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16, epoch: u64, validator_uid: u16)]
+pub struct RevealWeights<'info> {
+    #[account(
+        mut,
+        seeds = [b"consensus", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub consensus_state: Account<'info, ConsensusState>,
+    
+    #[account(
+        mut,
+        seeds = [b"weights", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref(), validator_uid.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub submission: Account<'info, WeightSubmissionAccount>,
+    
+    pub validator_signer: Signer<'info>,
+    
+    /// CHECK: Registry neuron for the signing hotkey, owned by the registry program
+    #[account(
+        seeds = [b"neuron", subnet_id.to_le_bytes().as_ref(), validator_signer.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16, epoch: u64, page: u8)]
+pub struct OpenMinerPage<'info> {
+    #[account(
+        seeds = [b"consensus", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub consensus_state: Account<'info, ConsensusState>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MinerPage::space(0),
+        seeds = [b"miner_page", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref(), &[page]],
+        bump
+    )]
+    pub miner_page: Account<'info, MinerPage>,
+    
+    /// Any keeper, paying the page's rent
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
     pub system_program: Program<'info, System>,
 }
 
@@ -303,53 +1564,272 @@ pub struct SubmitWeights<'info> {
 pub struct FinalizeConsensus<'info> {
     #[account(
         mut,
-        seeds = [b"consensus", &subnet_id.to_le_bytes(), &epoch.to_le_bytes()],
+        seeds = [b"consensus", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
         bump
     )]
     pub consensus_state: Account<'info, ConsensusState>,
     
-    /// CHECK: Authority (governor or automated finalizer)
+    /// CHECK: Authority (any keeper), paying for miner pages to grow
+    #[account(mut)]
     pub authority: Signer<'info>,
     
-    /// CHECK: Stake accounts for validators (variable length)
-    /// Multiple stake accounts passed for stake weight calculation
-    pub stake_accounts: Vec<AccountInfo<'info>>,
+    /// CHECK: Subnet consensus config PDA, defaults apply if it was never created
+    #[account(
+        seeds = [b"consensus_config", subnet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub consensus_config: UncheckedAccount<'info>,
     
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(subnet_id: u16, epoch: u64, validator_uid: u16)]
+pub struct CloseWeightSubmission<'info> {
+    #[account(
+        mut,
+        seeds = [b"weights", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref(), validator_uid.to_le_bytes().as_ref()],
+        bump,
+        has_one = validator,
+        close = validator
+    )]
+    pub submission: Account<'info, WeightSubmissionAccount>,
+    
+    /// CHECK: Epoch header PDA, finalized or already closed
+    #[account(
+        seeds = [b"consensus", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub consensus_state: UncheckedAccount<'info>,
+    
+    /// CHECK: Validator hotkey receiving the rent, checked by `has_one`
+    #[account(mut)]
+    pub validator: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16, epoch: u64, validator_uid: u16)]
+pub struct SlashValidator<'info> {
+    #[account(
+        mut,
+        seeds = [b"consensus", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub consensus_state: Account<'info, ConsensusState>,
+    
+    #[account(
+        seeds = [b"weights", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref(), validator_uid.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub submission: Account<'info, WeightSubmissionAccount>,
+    
+    /// CHECK: The submitting hotkey's stake account, validated by the staking program
+    #[account(
+        mut,
+        seeds = [b"stake", submission.validator.as_ref(), subnet_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = poi_staking::ID
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+    
+    /// CHECK: Subnet stake config, validated by the staking program
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the staking program accepts slashes from
+    #[account(seeds = [b"staking_authority"], bump)]
+    pub staking_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Staking treasury, validated by the staking program
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+    
+    /// CHECK: Validator alpha vault, validated by the staking program
+    #[account(mut)]
+    pub alpha_vault: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Treasury alpha token account, validated by the staking program
+    #[account(mut)]
+    pub treasury_alpha: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Token program, validated by the staking program
+    pub token_program: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Subnet validator set, validated by the staking program
+    #[account(mut)]
+    pub validator_set: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Registry neuron for the validator, validated by the staking program
+    #[account(mut)]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: Staking program's registry signer, validated by the staking program
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program, validated by the staking program
+    pub registry_program: UncheckedAccount<'info>,
+    
+    /// CHECK: Staking program
+    #[account(address = poi_staking::ID)]
+    pub staking_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16, epoch: u64, page: u8)]
+pub struct CloseMinerPage<'info> {
+    #[account(
+        mut,
+        seeds = [b"miner_page", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref(), &[page]],
+        bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub miner_page: Account<'info, MinerPage>,
+    
+    /// CHECK: Epoch header PDA, finalized or already closed
+    #[account(
+        seeds = [b"consensus", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub consensus_state: UncheckedAccount<'info>,
+    
+    /// CHECK: Page opener receiving the rent, checked by `has_one`
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16, epoch: u64)]
+pub struct CloseConsensusState<'info> {
+    #[account(
+        mut,
+        seeds = [b"consensus", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub consensus_state: Account<'info, ConsensusState>,
+    
+    /// CHECK: First committer receiving the rent, checked by `has_one`
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16, validator_uid: u16)]
+pub struct CloseValidatorBonds<'info> {
+    #[account(
+        mut,
+        seeds = [b"bonds", subnet_id.to_le_bytes().as_ref(), validator_uid.to_le_bytes().as_ref()],
+        bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub bonds: Account<'info, ValidatorBonds>,
+    
+    /// CHECK: Registry neuron of the bonds' hotkey, read if it still exists
+    #[account(
+        seeds = [b"neuron", subnet_id.to_le_bytes().as_ref(), bonds.validator.as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: Bonds creator receiving the rent, checked by `has_one`
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+/// Epoch header (`[b"consensus", subnet, epoch]`); weights live in one
+/// `WeightSubmissionAccount` per validator and miner consensus in `MinerPage`s
 #[account]
 pub struct ConsensusState {
     pub subnet_id: u16,
     pub epoch: u64,
-    pub submissions: Vec<WeightSubmission>,
-    pub miner_consensus: Vec<ConsensusEntry>,
+    /// First committer, refunded the rent when the header is closed
+    pub payer: Pubkey,
+    pub submission_count: u16,
+    pub revealed_count: u16,
+    pub mode: ConsensusMode,
+    pub kappa_bps: u64,
+    pub bond_alpha_bps: u64,
+    pub finalize_stage: FinalizeStage,
+    /// Miner pages holding votes, and those whose consensus is computed
+    /// (one bit per page)
+    pub miner_pages: u16,
+    pub computed_pages: u16,
+    /// Sum of every miner's rank, for incentive = rank / total rank
+    pub total_rank: u64,
+    /// Indexed by the validator slot votes are tagged with
+    pub validator_stakes: Vec<ValidatorStake>,
     pub validator_consensus: Vec<ConsensusEntry>,
     pub finalized: bool,
     pub finalized_at: i64,
 }
 
 impl ConsensusState {
-    pub const LEN: usize = 2 + 8 + 4 + (4 + 100 * WeightSubmission::LEN) + 4 + (4 + 100 * ConsensusEntry::LEN) + 4 + (4 + 100 * ConsensusEntry::LEN) + 1 + 8;
+    pub const LEN: usize = 2 + 8 + 32 + 2 + 2 + 1 + 8 + 8 + 1 + 2 + 2 + 8 + (4 + MAX_SUBMISSIONS * ValidatorStake::LEN) + (4 + MAX_SUBMISSIONS * ConsensusEntry::LEN) + 1 + 8;
+}
+
+/// Votes for and consensus of `MINERS_PER_PAGE` consecutive miner UIDs in an
+/// epoch (`[b"miner_page", subnet, epoch, page]`). Votes are collected while
+/// finalization accumulates submissions and dropped once the page's
+/// consensus is computed.
+#[account]
+pub struct MinerPage {
+    pub subnet_id: u16,
+    pub epoch: u64,
+    pub page: u8,
+    /// Keeper that opened the page, refunded the rent when it is closed
+    pub payer: Pubkey,
+    pub votes: Vec<MinerVote>,
+    /// Sorted by UID
+    pub miner_consensus: Vec<ConsensusEntry>,
+}
+
+impl MinerPage {
+    /// Space for a page holding `votes` votes
+    pub const fn space(votes: usize) -> usize {
+        2 + 8 + 1 + 32 + (4 + votes * MinerVote::LEN) + (4 + MINERS_PER_PAGE * ConsensusEntry::LEN)
+    }
 }
 
+/// One validator's weight for a miner, collected into the miner's page
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct WeightSubmission {
+pub struct MinerVote {
+    pub miner_uid: u16,
+    /// Index of the validator in the epoch's `validator_stakes`
+    pub validator_slot: u8,
+    pub weight: u64,
+}
+
+impl MinerVote {
+    pub const LEN: usize = 2 + 1 + 8;
+}
+
+/// One validator's weights for an epoch (`[b"weights", subnet, epoch, validator_uid]`)
+#[account]
+pub struct WeightSubmissionAccount {
     pub validator_uid: u16,
+    pub validator: Pubkey,
     pub subnet_id: u16,
     pub epoch: u64,
     pub weights: Vec<WeightEntry>,
     pub timestamp: i64,
+    pub last_update_slot: u64,
+    pub commit_hash: [u8; 32],
+    pub revealed: bool,
+    /// Validator stake weight as of the epoch start, fixed at commit time
+    pub stake_weight: u64,
 }
 
-impl WeightSubmission {
-    pub const LEN: usize = 2 + 2 + 8 + 4 + (4 + 100 * WeightEntry::LEN) + 8;
+impl WeightSubmissionAccount {
+    pub const LEN: usize = 2 + 32 + 2 + 8 + (4 + MAX_WEIGHTS * WeightEntry::LEN) + 8 + 8 + 32 + 1 + 8;
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct WeightEntry {
     pub miner_uid: u16,
-    pub weight: u64, // Normalized 0-10000
+    pub weight: u64, // Relative; normalized against the submission's total
 }
 
 impl WeightEntry {
@@ -362,15 +1842,106 @@ pub struct ConsensusEntry {
     pub consensus_weight: u64,
     pub trust_score: u64,
     pub emission_share: u64,
+    // Yuma outputs: rank and incentive for miners, dividends for validators
+    pub rank: u64,
+    pub incentive: u64,
+    pub dividends: u64,
 }
 
 impl ConsensusEntry {
-    pub const LEN: usize = 2 + 8 + 8 + 8;
+    pub const LEN: usize = 2 + 8 + 8 + 8 + 8 + 8 + 8;
+}
+
+/// How a subnet's epochs reach consensus
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConsensusMode {
+    /// Stake-weighted median with 2σ clipping
+    #[default]
+    Median,
+    /// Yuma consensus: κ clipping, EMA bonds and dividends
+    Yuma,
+}
+
+/// Per-subnet consensus settings (`[b"consensus_config", subnet]`)
+#[account]
+pub struct ConsensusConfig {
+    pub subnet_id: u16,
+    pub mode: ConsensusMode,
+    pub kappa_bps: u64,
+    pub bond_alpha_bps: u64,
+}
+
+impl ConsensusConfig {
+    pub const LEN: usize = 2 + 1 + 8 + 8;
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        Self {
+            subnet_id: 0,
+            mode: ConsensusMode::Median,
+            kappa_bps: DEFAULT_KAPPA_BPS,
+            bond_alpha_bps: DEFAULT_BOND_ALPHA_BPS,
+        }
+    }
+}
+
+/// A validator's bonds to miners, kept across epochs (`[b"bonds", subnet, validator_uid]`)
+#[account]
+pub struct ValidatorBonds {
+    pub subnet_id: u16,
+    pub validator_uid: u16,
+    /// Hotkey the bonds belong to; they restart when another hotkey commits
+    /// under the UID
+    pub validator: Pubkey,
+    /// Last epoch folded into the bonds; epochs skipped since are decayed
+    /// at the next update
+    pub last_epoch: u64,
+    pub bonds: Vec<BondEntry>,
+    /// Refunded the rent when the bonds are closed
+    pub payer: Pubkey,
+}
+
+impl ValidatorBonds {
+    pub const LEN: usize = 2 + 2 + 32 + 8 + (4 + MAX_CONSENSUS_MINERS * BondEntry::LEN) + 32;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BondEntry {
+    pub miner_uid: u16,
+    pub bond: u64, // Fraction of CONSENSUS_PRECISION
+}
+
+impl BondEntry {
+    pub const LEN: usize = 2 + 8;
+}
+
+/// A submitting validator's stake, recorded during finalization
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ValidatorStake {
+    pub validator_uid: u16,
+    pub stake_weight: u64,
+    pub revealed: bool,
+    /// Miner pages the validator's weights fall in, and those they have
+    /// been added to (one bit per page)
+    pub pages: u16,
+    pub absorbed_pages: u16,
+    pub slashed: bool,
+}
+
+impl ValidatorStake {
+    pub const LEN: usize = 2 + 8 + 1 + 2 + 2 + 1;
 }
 
-struct TrustScores {
-    miner_trust: Vec<(u16, u64)>,
-    validator_trust: Vec<ConsensusEntry>,
+/// Step of the cranked finalization an epoch is at
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FinalizeStage {
+    /// Recording stake and collecting votes into miner pages
+    Accumulate,
+    /// Computing each miner page's consensus
+    Consensus,
+    /// Scoring validators against the consensus
+    Trust,
 }
 
 #[error_code]
@@ -385,5 +1956,271 @@ pub enum ConsensusError {
     NotValidator,
     #[msg("Insufficient submissions")]
     InsufficientSubmissions,
+    #[msg("Stake account missing for a submitting validator")]
+    MissingStakeAccount,
+    #[msg("Stake account is not owned by the staking program")]
+    InvalidStakeAccount,
+    #[msg("Invalid registry neuron")]
+    InvalidNeuron,
+    #[msg("Weights reference an unregistered miner UID")]
+    UnregisteredMiner,
+    #[msg("Weights were updated too recently")]
+    WeightsRateLimited,
+    #[msg("Epoch already holds the maximum number of submissions")]
+    SubmissionsFull,
+    #[msg("Not allowed in the current commit-reveal phase")]
+    WrongPhase,
+    #[msg("Revealed weights do not match the commitment")]
+    CommitMismatch,
+    #[msg("Weights already revealed")]
+    AlreadyRevealed,
+    #[msg("Too many weight entries")]
+    TooManyWeights,
+    #[msg("Submission account does not belong to this epoch")]
+    InvalidSubmission,
+    #[msg("Not every submission of the epoch was passed")]
+    MissingSubmissions,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Invalid consensus config")]
+    InvalidConsensusConfig,
+    #[msg("Submission is missing its validator bonds account")]
+    MissingBonds,
+    #[msg("Weights must be sorted by miner UID without duplicates")]
+    UnsortedWeights,
+    #[msg("Miner page does not belong to this epoch or stage")]
+    InvalidMinerPage,
+    #[msg("Miner page holding a needed consensus was not passed")]
+    MissingMinerPage,
+    #[msg("Epoch is not finalized")]
+    EpochNotFinalized,
+    #[msg("Epoch consensus is still within its retention window")]
+    ConsensusRetained,
+    #[msg("Bonds belong to a current validator")]
+    BondsInUse,
+    #[msg("Validator already slashed for this epoch")]
+    AlreadySlashed,
+    #[msg("Validator's weights do not warrant a slash")]
+    NotSlashable,
+    #[msg("Stake snapshot is full and does not hold the validator")]
+    StakeSnapshotFull,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic xorshift64 generator for property checks
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+
+        /// Up to 16 (weight, stake) pairs; small weight ranges force ties
+        fn pairs(&mut self, max_weight: u64, max_stake: u64) -> Vec<(u64, u64)> {
+            let len = 1 + self.below(16) as usize;
+            (0..len)
+                .map(|_| (self.below(max_weight + 1), self.below(max_stake + 1)))
+                .collect()
+        }
+
+        fn shuffle(&mut self, pairs: &mut [(u64, u64)]) {
+            for i in (1..pairs.len()).rev() {
+                let j = self.below(i as u64 + 1) as usize;
+                pairs.swap(i, j);
+            }
+        }
+    }
+
+    const CASES: usize = 500;
+
+    #[test]
+    fn weighted_median_is_lower_median_of_generated_inputs() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..CASES {
+            let mut pairs = rng.pairs(20, 10);
+            pairs.sort_unstable();
+            let median = weighted_median(&pairs);
+
+            let total_stake: u128 = pairs.iter().map(|(_, s)| *s as u128).sum();
+            let share = |(_, s): &(u64, u64)| if total_stake == 0 { 1 } else { *s as u128 };
+            let total = if total_stake == 0 { pairs.len() as u128 } else { total_stake };
+            let below: u128 = pairs.iter().filter(|(w, _)| *w < median).map(share).sum();
+            let at_or_below: u128 = pairs.iter().filter(|(w, _)| *w <= median).map(share).sum();
+
+            // Smallest weight whose cumulative stake reaches half the total
+            assert!(below * 2 < total, "{:?} -> {}", pairs, median);
+            assert!(at_or_below * 2 >= total, "{:?} -> {}", pairs, median);
+        }
+    }
+
+    #[test]
+    fn weighted_median_ties_resolve_to_lower_weight() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..CASES {
+            let stake = 1 + rng.below(1_000);
+            let low = rng.below(1_000);
+            let high = low + 1 + rng.below(1_000);
+            assert_eq!(weighted_median(&[(low, stake), (high, stake)]), low);
+            assert_eq!(weighted_median(&[(low, 0), (high, 0)]), low);
+        }
+        assert_eq!(weighted_median(&[]), 0);
+    }
+
+    #[test]
+    fn miner_consensus_is_independent_of_submission_order() {
+        let mut rng = Rng(0xdead_beef_cafe_f00d);
+        for _ in 0..CASES {
+            let mut pairs = rng.pairs(10_000, 100);
+            let expected = miner_consensus_entry(1, &mut pairs.clone());
+
+            for _ in 0..4 {
+                rng.shuffle(&mut pairs);
+                let entry = miner_consensus_entry(1, &mut pairs.clone());
+                assert_eq!(entry.consensus_weight, expected.consensus_weight);
+                assert_eq!(entry.trust_score, expected.trust_score);
+            }
+        }
+    }
+
+    #[test]
+    fn yuma_consensus_is_independent_of_submission_order() {
+        let mut rng = Rng(0x5851_f42d_4c95_7f2d);
+        for _ in 0..CASES {
+            let mut pairs = rng.pairs(CONSENSUS_PRECISION, 100);
+            let total_stake: u128 = pairs.iter().map(|(_, s)| *s as u128).sum();
+            let expected = yuma_miner_entry(1, &mut pairs.clone(), total_stake, DEFAULT_KAPPA_BPS);
+
+            for _ in 0..4 {
+                rng.shuffle(&mut pairs);
+                let entry = yuma_miner_entry(1, &mut pairs.clone(), total_stake, DEFAULT_KAPPA_BPS);
+                assert_eq!(entry.consensus_weight, expected.consensus_weight);
+                assert_eq!(entry.trust_score, expected.trust_score);
+                assert_eq!(entry.rank, expected.rank);
+            }
+        }
+    }
+
+    #[test]
+    fn clip_outliers_drops_weights_outside_two_sigma() {
+        let mut weights = vec![(100, 1); 9];
+        weights.push((10_000, 1));
+        assert_eq!(clip_outliers(100, &weights), 100);
+    }
+
+    #[test]
+    fn clip_outliers_is_stake_weighted() {
+        // Both within the band: the mean leans toward the heavier stake
+        let weights = [(100, 3), (200, 1)];
+        assert_eq!(clip_outliers(100, &weights), 125);
+    }
+
+    #[test]
+    fn clip_outliers_without_stake_counts_validators_equally() {
+        let weights = [(100, 0), (200, 0)];
+        assert_eq!(clip_outliers(100, &weights), 150);
+        assert_eq!(clip_outliers(100, &[]), 100);
+    }
+
+    #[test]
+    fn consensus_math_does_not_overflow_at_u64_max() {
+        let max = u64::MAX;
+
+        assert_eq!(weighted_median(&[(1, max), (2, max), (3, max)]), 2);
+        assert_eq!(clip_outliers(max, &[(max, max), (max, max)]), max);
+
+        // Squared deviations near 2^128 saturate, and the outlier is still clipped
+        assert_eq!(clip_outliers(0, &[(0, max), (max, max), (max - 1, 1)]), 0);
+
+        let mut rng = Rng(0x0123_4567_89ab_cdef);
+        for _ in 0..CASES {
+            let mut pairs: Vec<(u64, u64)> = rng
+                .pairs(max - 1, max - 1)
+                .into_iter()
+                .map(|(w, s)| (max - w % 4, max - s % 4))
+                .collect();
+            let entry = miner_consensus_entry(1, &mut pairs);
+            assert!(entry.consensus_weight >= max - 3);
+        }
+
+        let mut weights = [(CONSENSUS_PRECISION, max), (CONSENSUS_PRECISION, max)];
+        let entry = yuma_miner_entry(1, &mut weights, 2 * max as u128, DEFAULT_KAPPA_BPS);
+        assert_eq!(entry.consensus_weight, CONSENSUS_PRECISION);
+        assert_eq!(entry.rank, CONSENSUS_PRECISION);
+        assert_eq!(entry.trust_score, CONSENSUS_PRECISION);
+    }
+
+    #[test]
+    fn fraction_handles_full_u128_range() {
+        assert_eq!(fraction(1, 3), 333_333_333);
+        assert_eq!(fraction(5, 0), 0);
+        assert_eq!(fraction(u128::MAX, u128::MAX), CONSENSUS_PRECISION);
+        assert_eq!(fraction(u128::MAX / 2, u128::MAX), 499_999_999);
+        assert_eq!(fraction(u128::MAX, 1), u64::MAX);
+    }
+
+    #[test]
+    fn bond_retention_compounds_per_epoch() {
+        assert_eq!(bond_retention(1_000, 0), CONSENSUS_PRECISION);
+        assert_eq!(bond_retention(1_000, 1), 900_000_000);
+        assert_eq!(bond_retention(1_000, 2), 810_000_000);
+        assert_eq!(bond_retention(0, u64::MAX), CONSENSUS_PRECISION);
+        assert_eq!(bond_retention(1_000, u64::MAX), 0);
+    }
+
+    #[test]
+    fn weights_commitment_matches_client_layout() {
+        let validator = Pubkey::new_from_array(core::array::from_fn(|i| i as u8 + 1));
+        let weights = vec![
+            WeightEntry { miner_uid: 1, weight: 6_000 },
+            WeightEntry { miner_uid: 2, weight: 4_000 },
+        ];
+        let salt = [9u8; 32];
+
+        // Same vector as tests/unit/serialization.test.ts
+        let commitment = weights_commitment(&validator, 7, 42, &weights, &salt);
+        let hex: String = commitment.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, "dec7840e5d62380993d6ad108b9832f98c118e74807833c1df93227887d05d1d");
+
+        assert_ne!(weights_commitment(&Pubkey::default(), 7, 42, &weights, &salt), commitment);
+        assert_ne!(weights_commitment(&validator, 8, 42, &weights, &salt), commitment);
+        assert_ne!(weights_commitment(&validator, 7, 43, &weights, &salt), commitment);
+    }
+
+    #[test]
+    fn empty_reveals_abstain_instead_of_being_slashed() {
+        let validator = |revealed: bool, pages: u16| ValidatorStake {
+            validator_uid: 1,
+            stake_weight: 100,
+            revealed,
+            pages,
+            absorbed_pages: pages,
+            slashed: false,
+        };
+
+        assert!(abstains(&[]));
+        assert!(abstains(&[WeightEntry { miner_uid: 1, weight: 0 }]));
+        assert!(!abstains(&[WeightEntry { miner_uid: 1, weight: 0 }, WeightEntry { miner_uid: 2, weight: 1 }]));
+
+        // Abstentions score no trust but are not deviations
+        assert!(slash_penalty(&validator(true, 0), 0).is_err());
+        assert_eq!(
+            slash_penalty(&validator(true, 1), 0).unwrap(),
+            (DEVIATION_SLASH_BPS, SlashReason::ConsensusDeviation)
+        );
+        assert!(slash_penalty(&validator(true, 1), SLASH_TRUST_THRESHOLD).is_err());
+        assert_eq!(
+            slash_penalty(&validator(false, 0), 0).unwrap(),
+            (UNREVEALED_SLASH_BPS, SlashReason::InvalidWeights)
+        );
+    }
+}
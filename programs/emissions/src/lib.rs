@@ -1,7 +1,7 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{self, Mint, Token, TokenAccount};
 
-declare_id!("poiEmissions111111111111111111111111");
+declare_id!("poiEmissions1111111111111111111111111111111");
 
 #[program]
 pub mod poi_emissions {
@@ -60,11 +60,15 @@ pub mod poi_emissions {
         Ok(())
     }
 
-    /// Claim accumulated rewards
+    /// Claim accumulated rewards. If the staking accounts are passed and the
+    /// claimant (the validator, or the delegator of `delegation`) opted into
+    /// auto-compounding, their staking rewards are restaked through the
+    /// staking program as part of the claim.
     pub fn claim_rewards(
         ctx: Context<ClaimRewards>,
         subnet_id: u16,
     ) -> Result<()> {
+        let compound = auto_compound_opted_in(&ctx.accounts)?;
         let emission_balance = &mut ctx.accounts.emission_balance;
         let neuron = &ctx.accounts.neuron;
 
@@ -73,19 +77,25 @@ pub mod poi_emissions {
             EmissionsError::InvalidSubnet
         );
         require!(
-            emission_balance.accumulated > 0,
+            emission_balance.accumulated > 0 || compound,
             EmissionsError::NoRewardsToClaim
         );
 
         let amount = emission_balance.accumulated;
-        emission_balance.accumulated = 0;
-        emission_balance.claimed += amount;
+        if amount > 0 {
+            emission_balance.accumulated = 0;
+            emission_balance.claimed += amount;
 
-        // Transfer tokens (would use SPL token transfer in production)
-        // For now, we just update the balance
+            // Transfer tokens (would use SPL token transfer in production)
+            // For now, we just update the balance
 
-        msg!("Neuron {} claimed {} α tokens from subnet {}", 
-             neuron.uid, amount, subnet_id);
+            msg!("Neuron {} claimed {} α tokens from subnet {}", 
+                 neuron.uid, amount, subnet_id);
+        }
+
+        if compound {
+            compound_staking_rewards(&ctx)?;
+        }
 
         Ok(())
     }
@@ -153,9 +163,93 @@ pub struct ClaimRewards<'info> {
     #[account(mut)]
     pub claimant: Signer<'info>,
     
+    /// CHECK: Validator stake account to compound, validated by the staking program
+    #[account(mut)]
+    pub stake_account: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Subnet stake config, validated by the staking program
+    pub stake_config: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Current epoch's stake snapshot, validated by the staking program
+    #[account(mut)]
+    pub stake_snapshot: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Claimant's delegation, validated by the staking program
+    #[account(mut)]
+    pub delegation: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: PDA signer the staking program accepts compounding from
+    #[account(seeds = [b"staking_authority"], bump)]
+    pub staking_authority: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Registry neuron for the validator, validated by the staking program
+    #[account(mut)]
+    pub stake_neuron: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Staking program's registry signer, validated by the staking program
+    pub registry_authority: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Registry program, validated by the staking program
+    pub registry_program: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Staking program
+    #[account(address = poi_staking::ID)]
+    pub staking_program: Option<UncheckedAccount<'info>>,
+    
     pub system_program: Program<'info, System>,
 }
 
+/// Whether the claimant opted into restaking. Without staking accounts there
+/// is nothing to compound.
+fn auto_compound_opted_in(accounts: &ClaimRewards) -> Result<bool> {
+    let Some(stake_info) = &accounts.stake_account else {
+        return Ok(false);
+    };
+    require_keys_eq!(*stake_info.owner, poi_staking::ID, EmissionsError::Unauthorized);
+    let stake_account = poi_staking::StakeAccount::try_deserialize(&mut stake_info.try_borrow_data()?.as_ref())?;
+
+    let claimant = accounts.claimant.key();
+    if claimant == stake_account.validator {
+        return Ok(stake_account.auto_compound);
+    }
+
+    let delegation_info = accounts.delegation.as_ref().ok_or(EmissionsError::Unauthorized)?;
+    require_keys_eq!(*delegation_info.owner, poi_staking::ID, EmissionsError::Unauthorized);
+    let delegation = poi_staking::Delegation::try_deserialize(&mut delegation_info.try_borrow_data()?.as_ref())?;
+    require_keys_eq!(delegation.delegator, claimant, EmissionsError::Unauthorized);
+
+    Ok(delegation.auto_compound)
+}
+
+/// Restake the claimant's staking rewards, signed as the emissions program
+fn compound_staking_rewards(ctx: &Context<ClaimRewards>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let missing = || error!(EmissionsError::MissingStakingAccounts);
+
+    let cpi_accounts = poi_staking::cpi::accounts::CompoundRewards {
+        stake_account: accounts.stake_account.as_ref().ok_or_else(missing)?.to_account_info(),
+        stake_config: accounts.stake_config.as_ref().ok_or_else(missing)?.to_account_info(),
+        stake_snapshot: accounts.stake_snapshot.as_ref().ok_or_else(missing)?.to_account_info(),
+        delegation: accounts.delegation.as_ref().map(|d| d.to_account_info()),
+        emissions_authority: accounts.staking_authority.as_ref().ok_or_else(missing)?.to_account_info(),
+        payer: accounts.claimant.to_account_info(),
+        neuron: accounts.stake_neuron.as_ref().ok_or_else(missing)?.to_account_info(),
+        registry_authority: accounts.registry_authority.as_ref().ok_or_else(missing)?.to_account_info(),
+        registry_program: accounts.registry_program.as_ref().ok_or_else(missing)?.to_account_info(),
+        system_program: accounts.system_program.to_account_info(),
+    };
+    let staking_authority_bump = ctx.bumps.staking_authority.ok_or_else(missing)?;
+
+    poi_staking::cpi::compound_rewards(
+        CpiContext::new_with_signer(
+            accounts.staking_program.as_ref().ok_or_else(missing)?.to_account_info(),
+            cpi_accounts,
+            &[&[b"staking_authority", &[staking_authority_bump]]],
+        ),
+        accounts.claimant.key(),
+    )
+}
+
 #[derive(Accounts)]
 pub struct CrossSubnetAllocation<'info> {
     /// CHECK: Hive program
@@ -197,5 +291,7 @@ pub enum EmissionsError {
     NoRewardsToClaim,
     #[msg("Unauthorized")]
     Unauthorized,
+    #[msg("Staking accounts are required to compound rewards")]
+    MissingStakingAccounts,
 }
 
@@ -19,6 +19,10 @@ pub enum RegistryError {
     InvalidNeuron,
     #[error("Neuron is still in immunity period")]
     NeuronImmune,
+    #[error("Subnet still has registered neurons")]
+    SubnetNotEmpty,
+    #[error("Subnet already exists")]
+    SubnetAlreadyExists,
 }
 
 impl From<RegistryError> for ProgramError {
@@ -27,7 +27,15 @@ pub enum RegistryInstruction {
         trust: Option<u64>,
         incentive: Option<u64>,
         validator_trust: Option<u64>,
-        is_validator: Option<bool>,
+    },
+    DissolveSubnet {
+        subnet_id: u16,
+    },
+    UpdateNeuronStake {
+        stake: u64,
+    },
+    UpdateNeuronPermit {
+        is_validator: bool,
     },
 }
 
@@ -146,18 +154,8 @@ impl RegistryInstruction {
                     None
                 };
                 let validator_trust = if data.len() > offset + 8 && data[offset] != 0 {
-                    let val = u64::from_le_bytes(data[offset + 1..offset + 9].try_into().unwrap());
-                    offset += 9;
-                    Some(val)
+                    Some(u64::from_le_bytes(data[offset + 1..offset + 9].try_into().unwrap()))
                 } else {
-                    offset += 1;
-                    None
-                };
-                let is_validator = if data.len() > offset && data[offset] != 0 {
-                    offset += 1;
-                    Some(data[offset - 1] != 0)
-                } else {
-                    offset += 1;
                     None
                 };
                 Ok(RegistryInstruction::UpdateNeuronStatus {
@@ -165,7 +163,31 @@ impl RegistryInstruction {
                     trust,
                     incentive,
                     validator_trust,
-                    is_validator,
+                })
+            }
+            5 => {
+                // DissolveSubnet
+                if data.len() < 2 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let subnet_id = u16::from_le_bytes([data[0], data[1]]);
+                Ok(RegistryInstruction::DissolveSubnet { subnet_id })
+            }
+            6 => {
+                // UpdateNeuronStake
+                if data.len() < 8 {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                let stake = u64::from_le_bytes(data[0..8].try_into().unwrap());
+                Ok(RegistryInstruction::UpdateNeuronStake { stake })
+            }
+            7 => {
+                // UpdateNeuronPermit
+                if data.is_empty() {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                Ok(RegistryInstruction::UpdateNeuronPermit {
+                    is_validator: data[0] != 0,
                 })
             }
             _ => Err(ProgramError::InvalidInstructionData),
@@ -2,9 +2,11 @@ use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint,
     entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     pubkey::Pubkey,
-    sysvar::{clock::Clock, Sysvar},
+    system_instruction,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
 pub mod error;
@@ -13,11 +15,17 @@ pub mod state;
 
 pub use error::RegistryError;
 pub use instruction::RegistryInstruction;
-pub use state::{Neuron, Subnet};
+pub use state::{NetworkState, Neuron, Subnet};
 
 // Program ID - Deployment address for testnet
 solana_program::declare_id!("iJUv5HxvwXFZaGeNDEG1DCNWYNfLQke8SBGvkrKYP2u");
 
+/// Staking program allowed to mirror stake onto neurons
+pub const STAKING_PROGRAM_ID: Pubkey = solana_program::pubkey!("poiStaking111111111111111111111111111111111");
+
+/// Consensus program allowed to write neuron rank and trust
+pub const CONSENSUS_PROGRAM_ID: Pubkey = solana_program::pubkey!("poiConsensus1111111111111111111111111111111");
+
 entrypoint!(process_instruction);
 
 pub fn process_instruction(
@@ -63,8 +71,16 @@ pub fn process_instruction(
             trust,
             incentive,
             validator_trust,
-            is_validator,
-        } => process_update_neuron_status(accounts, rank, trust, incentive, validator_trust, is_validator),
+        } => process_update_neuron_status(program_id, accounts, rank, trust, incentive, validator_trust),
+        RegistryInstruction::DissolveSubnet { subnet_id } => {
+            process_dissolve_subnet(program_id, accounts, subnet_id)
+        }
+        RegistryInstruction::UpdateNeuronStake { stake } => {
+            process_update_neuron_stake(program_id, accounts, stake)
+        }
+        RegistryInstruction::UpdateNeuronPermit { is_validator } => {
+            process_update_neuron_permit(program_id, accounts, is_validator)
+        }
     }
 }
 
@@ -80,7 +96,8 @@ fn process_create_subnet(
     let accounts_iter = &mut accounts.iter();
     let subnet_account = next_account_info(accounts_iter)?;
     let governor = next_account_info(accounts_iter)?;
-    let _system_program = next_account_info(accounts_iter)?;
+    let system_program = next_account_info(accounts_iter)?;
+    let network_account = next_account_info(accounts_iter)?;
 
     // Validate inputs
     if max_neurons > 255 {
@@ -110,8 +127,30 @@ fn process_create_subnet(
         return Err(ProgramError::InvalidAccountData);
     }
 
-    // Initialize subnet account
+    // Re-creating a live subnet would orphan its lock
+    if subnet_account.data_len() < state::SUBNET_LEN {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    if Subnet::deserialize(&subnet_account.try_borrow_data()?)?.governor != Pubkey::default() {
+        return Err(RegistryError::SubnetAlreadyExists.into());
+    }
+
+    // Lock the current network lock cost into the network PDA
     let clock = Clock::get()?;
+    let mut network = load_network_state(program_id, network_account, governor, system_program)?;
+    let lock_cost = network.lock_cost(clock.unix_timestamp);
+
+    invoke(
+        &system_instruction::transfer(governor.key, network_account.key, lock_cost),
+        &[governor.clone(), network_account.clone(), system_program.clone()],
+    )?;
+
+    network.last_lock_cost = lock_cost;
+    network.last_lock_at = clock.unix_timestamp;
+    network.total_locked = network.total_locked.saturating_add(lock_cost);
+    network.serialize(&mut network_account.try_borrow_mut_data()?)?;
+
+    // Initialize subnet account
     let subnet = Subnet {
         id: subnet_id,
         governor: *governor.key,
@@ -121,17 +160,59 @@ fn process_create_subnet(
         emission_rate,
         created_at: clock.unix_timestamp,
         neuron_count: 0,
+        locked_amount: lock_cost,
     };
 
     // Serialize and write to account
     let mut data = subnet_account.try_borrow_mut_data()?;
     subnet.serialize(&mut data)?;
 
-    solana_program::msg!("Subnet {} created by governor {}", subnet_id, governor.key);
+    solana_program::msg!(
+        "Subnet {} created by governor {} (locked {} lamports)",
+        subnet_id,
+        governor.key,
+        lock_cost
+    );
 
     Ok(())
 }
 
+/// Load the network PDA, creating it on first use with `payer` funding rent
+fn load_network_state<'a>(
+    program_id: &Pubkey,
+    network_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> Result<NetworkState, ProgramError> {
+    let (expected_network_pubkey, bump) = Pubkey::find_program_address(&[b"network"], program_id);
+
+    if *network_account.key != expected_network_pubkey {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    if network_account.data_is_empty() {
+        let rent = Rent::get()?;
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                network_account.key,
+                rent.minimum_balance(state::NETWORK_LEN),
+                state::NETWORK_LEN as u64,
+                program_id,
+            ),
+            &[payer.clone(), network_account.clone(), system_program.clone()],
+            &[&[b"network", &[bump]]],
+        )?;
+        return Ok(NetworkState::default());
+    }
+
+    if network_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    NetworkState::deserialize(&network_account.try_borrow_data()?)
+}
+
 fn process_register_neuron(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
@@ -335,28 +416,25 @@ fn process_prune_neuron(
     Ok(())
 }
 
+/// Consensus outputs only; `is_validator` follows the staking program's
+/// permit through `UpdateNeuronPermit`
 fn process_update_neuron_status(
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     rank: Option<u64>,
     trust: Option<u64>,
     incentive: Option<u64>,
     validator_trust: Option<u64>,
-    is_validator: Option<bool>,
 ) -> ProgramResult {
     let accounts_iter = &mut accounts.iter();
     let neuron_account = next_account_info(accounts_iter)?;
-    let authority = next_account_info(accounts_iter)?;
-    let consensus_program = next_account_info(accounts_iter)?;
-    let staking_program = next_account_info(accounts_iter)?;
+    let consensus_authority = next_account_info(accounts_iter)?;
 
-    // Verify authority is signer
-    if !authority.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
+    verify_program_authority(consensus_authority, &CONSENSUS_PROGRAM_ID)?;
 
-    // Verify authority is consensus or staking program
-    if *authority.key != *consensus_program.key && *authority.key != *staking_program.key {
-        return Err(RegistryError::Unauthorized.into());
+    // Verify program ownership
+    if neuron_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
     }
 
     // Deserialize neuron
@@ -376,13 +454,179 @@ fn process_update_neuron_status(
     if let Some(vt) = validator_trust {
         neuron.validator_trust = vt;
     }
-    if let Some(iv) = is_validator {
-        neuron.is_validator = iv;
+
+    // Serialize back
+    neuron.serialize(&mut neuron_data)?;
+
+    Ok(())
+}
+
+
+fn process_dissolve_subnet(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    subnet_id: u16,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let subnet_account = next_account_info(accounts_iter)?;
+    let governor = next_account_info(accounts_iter)?;
+    let network_account = next_account_info(accounts_iter)?;
+
+    // Verify governor is signer
+    if !governor.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Verify program ownership
+    if subnet_account.owner != program_id || network_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (expected_subnet_pubkey, _bump) = Pubkey::find_program_address(
+        &[b"subnet", &subnet_id.to_le_bytes()],
+        program_id,
+    );
+    let (expected_network_pubkey, _bump) = Pubkey::find_program_address(&[b"network"], program_id);
+
+    if *subnet_account.key != expected_subnet_pubkey || *network_account.key != expected_network_pubkey {
+        return Err(ProgramError::InvalidAccountData);
     }
 
+    // Deserialize subnet
+    let subnet = Subnet::deserialize(&subnet_account.try_borrow_data()?)?;
+
+    if subnet.id != subnet_id {
+        return Err(RegistryError::InvalidSubnet.into());
+    }
+    if subnet.governor != *governor.key {
+        return Err(RegistryError::Unauthorized.into());
+    }
+    if subnet.neuron_count > 0 {
+        return Err(RegistryError::SubnetNotEmpty.into());
+    }
+
+    // Release the locked cost from the network PDA
+    let mut network = NetworkState::deserialize(&network_account.try_borrow_data()?)?;
+    network.total_locked = network.total_locked.saturating_sub(subnet.locked_amount);
+    network.serialize(&mut network_account.try_borrow_mut_data()?)?;
+
+    // Refund the lock and close the subnet account, returning its rent too
+    let subnet_lamports = subnet_account.lamports();
+    let network_lamports = network_account
+        .lamports()
+        .checked_sub(subnet.locked_amount)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    let governor_lamports = governor
+        .lamports()
+        .checked_add(subnet.locked_amount)
+        .and_then(|lamports| lamports.checked_add(subnet_lamports))
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    **network_account.try_borrow_mut_lamports()? = network_lamports;
+    **subnet_account.try_borrow_mut_lamports()? = 0;
+    **governor.try_borrow_mut_lamports()? = governor_lamports;
+    subnet_account.try_borrow_mut_data()?.fill(0);
+
+    solana_program::msg!(
+        "Subnet {} dissolved, refunded {} lamports to governor {}",
+        subnet_id,
+        subnet.locked_amount,
+        governor.key
+    );
+
+    Ok(())
+}
+
+fn process_update_neuron_stake(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    stake: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let neuron_account = next_account_info(accounts_iter)?;
+    let staking_authority = next_account_info(accounts_iter)?;
+
+    verify_program_authority(staking_authority, &STAKING_PROGRAM_ID)?;
+
+    let mut neuron_data = neuron_account.try_borrow_mut_data()?;
+    let mut neuron = load_staked_neuron(program_id, neuron_account, &neuron_data)?;
+
+    neuron.stake = stake;
+
     // Serialize back
     neuron.serialize(&mut neuron_data)?;
 
+    solana_program::msg!("Neuron {} stake set to {} in subnet {}", neuron.uid, stake, neuron.subnet_id);
+
     Ok(())
 }
 
+fn process_update_neuron_permit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    is_validator: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let neuron_account = next_account_info(accounts_iter)?;
+    let staking_authority = next_account_info(accounts_iter)?;
+
+    verify_program_authority(staking_authority, &STAKING_PROGRAM_ID)?;
+
+    let mut neuron_data = neuron_account.try_borrow_mut_data()?;
+    let mut neuron = load_staked_neuron(program_id, neuron_account, &neuron_data)?;
+
+    neuron.is_validator = is_validator;
+
+    // Serialize back
+    neuron.serialize(&mut neuron_data)?;
+
+    solana_program::msg!(
+        "Neuron {} validator permit set to {} in subnet {}",
+        neuron.uid,
+        is_validator,
+        neuron.subnet_id
+    );
+
+    Ok(())
+}
+
+/// Only `program`'s `registry_authority` PDA can sign: the staking program's
+/// for stake and permits, the consensus program's for rank and trust
+fn verify_program_authority(authority: &AccountInfo, program: &Pubkey) -> ProgramResult {
+    let (expected_authority, _bump) =
+        Pubkey::find_program_address(&[b"registry_authority"], program);
+
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *authority.key != expected_authority {
+        return Err(RegistryError::Unauthorized.into());
+    }
+
+    Ok(())
+}
+
+/// Deserialize a registered neuron, checking ownership and its PDA
+fn load_staked_neuron(
+    program_id: &Pubkey,
+    neuron_account: &AccountInfo,
+    neuron_data: &[u8],
+) -> Result<Neuron, ProgramError> {
+    // Verify program ownership
+    if neuron_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let neuron = Neuron::deserialize(neuron_data)?;
+
+    let (expected_neuron_pubkey, _bump) = Pubkey::find_program_address(
+        &[b"neuron", &neuron.subnet_id.to_le_bytes(), neuron.hotkey.as_ref()],
+        program_id,
+    );
+
+    if *neuron_account.key != expected_neuron_pubkey || neuron.uid == 0 {
+        return Err(RegistryError::InvalidNeuron.into());
+    }
+
+    Ok(neuron)
+}
@@ -3,7 +3,10 @@ use solana_program::{
     pubkey::Pubkey,
 };
 
-pub const SUBNET_LEN: usize = 2 + 32 + 1 + 1 + 32 + 8 + 8 + 2; // 86 bytes
+pub const SUBNET_LEN: usize = 2 + 32 + 1 + 1 + 32 + 8 + 8 + 2 + 8; // 94 bytes
+/// Subnet accounts created before `locked_amount` was added (86 bytes). They
+/// are still read, with nothing locked, and written back without the field.
+pub const LEGACY_SUBNET_LEN: usize = SUBNET_LEN - 8;
 pub const NEURON_LEN: usize = 2 + 2 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 8; // 125 bytes
 
 #[derive(Debug, Clone)]
@@ -16,11 +19,13 @@ pub struct Subnet {
     pub emission_rate: u64,
     pub created_at: i64,
     pub neuron_count: u16,
+    pub locked_amount: u64,
 }
 
 impl Subnet {
     pub fn serialize(&self, data: &mut [u8]) -> Result<(), ProgramError> {
-        if data.len() < SUBNET_LEN {
+        // Legacy accounts have no room for a lock
+        if data.len() < SUBNET_LEN && (data.len() < LEGACY_SUBNET_LEN || self.locked_amount != 0) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -40,12 +45,16 @@ impl Subnet {
         data[offset..offset + 8].copy_from_slice(&self.created_at.to_le_bytes());
         offset += 8;
         data[offset..offset + 2].copy_from_slice(&self.neuron_count.to_le_bytes());
+        offset += 2;
+        if data.len() >= SUBNET_LEN {
+            data[offset..offset + 8].copy_from_slice(&self.locked_amount.to_le_bytes());
+        }
 
         Ok(())
     }
 
     pub fn deserialize(data: &[u8]) -> Result<Self, ProgramError> {
-        if data.len() < SUBNET_LEN {
+        if data.len() < LEGACY_SUBNET_LEN {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -70,6 +79,12 @@ impl Subnet {
         let created_at = i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
         offset += 8;
         let neuron_count = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        offset += 2;
+        let locked_amount = if data.len() >= SUBNET_LEN {
+            u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+        } else {
+            0
+        };
 
         Ok(Subnet {
             id,
@@ -80,6 +95,7 @@ impl Subnet {
             emission_rate,
             created_at,
             neuron_count,
+            locked_amount,
         })
     }
 }
@@ -189,3 +205,55 @@ impl Neuron {
     }
 }
 
+
+pub const NETWORK_LEN: usize = 8 + 8 + 8; // 24 bytes
+
+/// Floor for the subnet lock cost (1 SOL)
+pub const MIN_LOCK_COST: u64 = 1_000_000_000;
+/// Seconds for a doubled lock cost to decay back to the last lock cost (14 days)
+pub const LOCK_REDUCTION_INTERVAL: i64 = 1_209_600;
+
+/// Protocol-owned PDA (`[b"network"]`) that tracks the subnet lock cost and
+/// holds the lamports locked by every live subnet
+#[derive(Debug, Clone, Default)]
+pub struct NetworkState {
+    pub last_lock_cost: u64,
+    pub last_lock_at: i64,
+    pub total_locked: u64,
+}
+
+impl NetworkState {
+    /// Current cost of creating a subnet: the last lock cost doubled, decaying
+    /// linearly with time since the last creation, floored at `MIN_LOCK_COST`
+    pub fn lock_cost(&self, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.last_lock_at).max(0) as u128;
+        let last = self.last_lock_cost as u128;
+        let decay = last * elapsed / LOCK_REDUCTION_INTERVAL as u128;
+        let cost = (last * 2).saturating_sub(decay);
+        cost.clamp(MIN_LOCK_COST as u128, u64::MAX as u128) as u64
+    }
+
+    pub fn serialize(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if data.len() < NETWORK_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        data[0..8].copy_from_slice(&self.last_lock_cost.to_le_bytes());
+        data[8..16].copy_from_slice(&self.last_lock_at.to_le_bytes());
+        data[16..24].copy_from_slice(&self.total_locked.to_le_bytes());
+
+        Ok(())
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < NETWORK_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(NetworkState {
+            last_lock_cost: u64::from_le_bytes(data[0..8].try_into().unwrap()),
+            last_lock_at: i64::from_le_bytes(data[8..16].try_into().unwrap()),
+            total_locked: u64::from_le_bytes(data[16..24].try_into().unwrap()),
+        })
+    }
+}
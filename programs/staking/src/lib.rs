@@ -1,6 +1,84 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+};
 
-declare_id!("poiStaking1111111111111111111111111");
+declare_id!("poiStaking111111111111111111111111111111111");
+
+/// Registry program that owns Neuron accounts
+pub const REGISTRY_PROGRAM_ID: Pubkey = pubkey!("iJUv5HxvwXFZaGeNDEG1DCNWYNfLQke8SBGvkrKYP2u");
+
+/// Consensus program, the only caller allowed to slash
+pub const CONSENSUS_PROGRAM_ID: Pubkey = pubkey!("poiConsensus1111111111111111111111111111111");
+
+/// Emissions program, the only caller allowed to compound rewards
+pub const EMISSIONS_PROGRAM_ID: Pubkey = pubkey!("poiEmissions1111111111111111111111111111111");
+
+/// Unstake cooldown before a ticket can be redeemed (7 days)
+pub const UNSTAKE_COOLDOWN: i64 = 604800;
+
+/// Minimum time between redelegations of the same delegation (7 days)
+pub const REDELEGATE_INTERVAL: i64 = 604800;
+
+/// Upper bound on validator commission (50%)
+pub const MAX_COMMISSION_BPS: u16 = 5000;
+
+/// Delay before a commission change takes effect (7 days)
+pub const COMMISSION_CHANGE_DELAY: i64 = 604800;
+
+/// Fixed-point scale for `reward_per_share` and `delegation_scale`
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+/// Upper bound on a single slash (50%)
+pub const MAX_SLASH_BPS: u16 = 5000;
+
+/// Denominator for `SubnetStakeConfig` weights and ratios
+pub const WEIGHT_DENOMINATOR: u64 = 10000;
+
+/// Default weight of delegated stake (0.18)
+pub const DEFAULT_DELEGATION_WEIGHT: u64 = 1800;
+
+/// Upper bound on SOL and alpha conversion weights (100x)
+pub const MAX_CONVERSION_WEIGHT: u64 = 100 * WEIGHT_DENOMINATOR;
+
+/// Upper bound on the delegated-to-self stake cap (100x)
+pub const MAX_DELEGATION_RATIO: u64 = 100 * WEIGHT_DENOMINATOR;
+
+/// Length of a staking epoch in seconds (72 minutes)
+pub const EPOCH_DURATION: i64 = 4320;
+
+/// Validators recorded per stake snapshot
+pub const MAX_SNAPSHOT_VALIDATORS: usize = 128;
+
+/// Epochs a stake snapshot is kept for consensus before anyone can close it
+pub const SNAPSHOT_RETENTION_EPOCHS: u64 = 20;
+
+/// Upper bound on validator permits per subnet, regardless of the registry limit
+pub const MAX_VALIDATOR_PERMITS: usize = 128;
+
+/// Registry `UpdateNeuronStake` instruction tag
+const REGISTRY_UPDATE_NEURON_STAKE: u8 = 6;
+
+/// Delegation lock terms in seconds and their stake weight multipliers
+/// (over `WEIGHT_DENOMINATOR`): 30, 90 and 180 days
+pub const DELEGATION_LOCK_TIERS: [(i64, u16); 3] = [
+    (2_592_000, 11000),
+    (7_776_000, 12500),
+    (15_552_000, 15000),
+];
+
+/// Lock expiries are rounded up to whole days so a validator's pending
+/// expiries fit a bounded schedule
+pub const LOCK_EXPIRY_GRANULARITY: i64 = 86400;
+
+/// Distinct lock expiry days a validator can have outstanding
+pub const MAX_LOCK_EXPIRIES: usize = (DELEGATION_LOCK_TIERS[2].0 / LOCK_EXPIRY_GRANULARITY) as usize + 1;
+
+/// Registry `UpdateNeuronPermit` instruction tag
+const REGISTRY_UPDATE_NEURON_PERMIT: u8 = 7;
 
 #[program]
 pub mod poi_staking {
@@ -14,16 +92,49 @@ pub mod poi_staking {
     ) -> Result<()> {
         require!(amount > 0, StakingError::InvalidAmount);
 
-        let stake_account = &mut ctx.accounts.stake_account;
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.validator.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            subnet_id,
+            ctx.accounts.validator.key(),
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
 
         // Transfer SOL to stake account
-        **ctx.accounts.stake_account.to_account_info().try_borrow_mut_lamports()? += amount;
-        **ctx.accounts.validator.to_account_info().try_borrow_mut_lamports()? -= amount;
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.validator.to_account_info(),
+                    to: ctx.accounts.stake_account.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let stake_account = &mut ctx.accounts.stake_account;
 
         stake_account.validator = ctx.accounts.validator.key();
         stake_account.subnet_id = subnet_id;
         stake_account.amount += amount;
         stake_account.delegated_amount = 0;
+        stake_account.delegation_scale = REWARD_PRECISION;
+        stake_account.unstake_scale = REWARD_PRECISION;
+
+        require!(
+            meets_minimum(stake_account.amount, config.min_self_stake),
+            StakingError::BelowMinimumStake
+        );
+
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            calculate_stake_weight(stake_account, &config)?,
+        )?;
 
         msg!("Validator {} staked {} SOL in subnet {}", 
              ctx.accounts.validator.key(), amount, subnet_id);
@@ -31,6 +142,58 @@ pub mod poi_staking {
         Ok(())
     }
 
+    /// Add SOL to an existing validator stake account
+    pub fn add_stake(
+        ctx: Context<AddStake>,
+        subnet_id: u16,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.validator.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            subnet_id,
+            ctx.accounts.validator.key(),
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
+
+        // Transfer SOL to stake account
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.validator.to_account_info(),
+                    to: ctx.accounts.stake_account.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.amount += amount;
+
+        require!(
+            meets_minimum(stake_account.self_stake()?, config.min_self_stake),
+            StakingError::BelowMinimumStake
+        );
+
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            calculate_stake_weight(stake_account, &config)?,
+        )?;
+
+        msg!("Validator {} added {} SOL to stake in subnet {}", 
+             ctx.accounts.validator.key(), amount, subnet_id);
+
+        Ok(())
+    }
+
     /// Delegate stake to a validator
     pub fn delegate(
         ctx: Context<Delegate>,
@@ -39,38 +202,89 @@ pub mod poi_staking {
     ) -> Result<()> {
         require!(amount > 0, StakingError::InvalidAmount);
 
-        let delegation = &mut ctx.accounts.delegation;
-        let stake_account = &mut ctx.accounts.stake_account;
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.delegator.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            subnet_id,
+            ctx.accounts.validator.key(),
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
 
         // Transfer SOL to stake account
-        **ctx.accounts.stake_account.to_account_info().try_borrow_mut_lamports()? += amount;
-        **ctx.accounts.delegator.to_account_info().try_borrow_mut_lamports()? -= amount;
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.delegator.to_account_info(),
+                    to: ctx.accounts.stake_account.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let delegation = &mut ctx.accounts.delegation;
+        let stake_account = &mut ctx.accounts.stake_account;
 
-        if delegation.amount == 0 {
+        if delegation.delegator == Pubkey::default() {
             // New delegation
             delegation.delegator = ctx.accounts.delegator.key();
             delegation.validator = ctx.accounts.validator.key();
             delegation.subnet_id = subnet_id;
+            stake_account.delegation_count += 1;
         }
 
+        // A top-up is not locked: the bonus stays on the locked amount
+        let now = Clock::get()?.unix_timestamp;
+        delegation.apply_slash(stake_account.delegation_scale)?;
+        let old_lock = delegation.lock_entry();
+        delegation.release_expired_lock(now);
+        delegation.settle_rewards(stake_account.reward_per_share)?;
         delegation.amount += amount;
+        delegation.reset_reward_debt(stake_account.reward_per_share)?;
+        stake_account.update_delegation_bonus(old_lock, delegation.lock_entry(), now)?;
+
+        require!(
+            meets_minimum(delegation.amount, config.min_delegation),
+            StakingError::BelowMinimumStake
+        );
+
+        stake_account.amount += amount;
         stake_account.delegated_amount += amount;
 
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            calculate_stake_weight(stake_account, &config)?,
+        )?;
+
         msg!("Delegator {} delegated {} SOL to validator {} in subnet {}", 
              ctx.accounts.delegator.key(), amount, ctx.accounts.validator.key(), subnet_id);
 
         Ok(())
     }
 
-    /// Unstake SOL (with cooldown period)
+    /// Unstake SOL into a per-request ticket (with cooldown period)
     pub fn unstake(
         ctx: Context<Unstake>,
         amount: u64,
     ) -> Result<()> {
         require!(amount > 0, StakingError::InvalidAmount);
 
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.stake_account.subnet_id,
+            ctx.accounts.stake_account.validator,
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
+
         let stake_account = &mut ctx.accounts.stake_account;
-        let neuron = &mut ctx.accounts.neuron;
 
         require!(
             stake_account.amount >= amount,
@@ -79,220 +293,3033 @@ pub mod poi_staking {
 
         // Check if unstaking from own stake or delegation
         let is_validator_unstake = ctx.accounts.authority.key() == stake_account.validator;
-        let is_delegator_unstake = ctx.accounts.delegation.is_some();
 
         if is_validator_unstake {
             require!(
-                stake_account.amount - stake_account.delegated_amount >= amount,
+                stake_account.self_stake()? >= amount,
                 StakingError::CannotUnstakeDelegated
             );
             stake_account.amount -= amount;
+            require!(
+                meets_minimum(stake_account.self_stake()?, config.min_self_stake),
+                StakingError::BelowMinimumStake
+            );
         } else if let Some(delegation) = &mut ctx.accounts.delegation {
+            require!(
+                delegation.delegator == ctx.accounts.authority.key()
+                    && delegation.validator == stake_account.validator
+                    && delegation.subnet_id == stake_account.subnet_id,
+                StakingError::Unauthorized
+            );
+            delegation.apply_slash(stake_account.delegation_scale)?;
             require!(
                 delegation.amount >= amount,
                 StakingError::InsufficientStake
             );
+            let now = Clock::get()?.unix_timestamp;
+            require!(!delegation.is_locked(now), StakingError::DelegationLocked);
+            let old_lock = delegation.lock_entry();
+            delegation.release_expired_lock(now);
+            delegation.settle_rewards(stake_account.reward_per_share)?;
             delegation.amount -= amount;
-            stake_account.delegated_amount -= amount;
+            delegation.reset_reward_debt(stake_account.reward_per_share)?;
+            stake_account.update_delegation_bonus(old_lock, delegation.lock_entry(), now)?;
+            require!(
+                meets_minimum(delegation.amount, config.min_delegation),
+                StakingError::BelowMinimumStake
+            );
+            stake_account.amount -= amount;
+            // Lazily slashed delegations can round above the aggregate by dust
+            stake_account.delegated_amount = stake_account.delegated_amount.saturating_sub(amount);
         } else {
             return Err(StakingError::Unauthorized.into());
         }
 
-        // Set cooldown period (7 days)
-        stake_account.unstake_cooldown_until = Clock::get()?.unix_timestamp + 604800;
-        stake_account.unstake_pending += amount;
+        // Lock the amount in a ticket that only its owner can redeem
+        let ticket = &mut ctx.accounts.ticket;
+        ticket.owner = ctx.accounts.authority.key();
+        ticket.stake_account = stake_account.key();
+        ticket.unlock_at = Clock::get()?.unix_timestamp + UNSTAKE_COOLDOWN;
+        stake_account.open_ticket(ticket, StakeAsset::Sol, amount)?;
 
-        msg!("Unstaked {} SOL from validator {} in subnet {}", 
-             amount, ctx.accounts.validator.key(), stake_account.subnet_id);
+        let weight = calculate_stake_weight(stake_account, &config)?;
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            weight,
+        )?;
+
+        if refresh_permit(stake_account, ctx.accounts.validator_set.as_mut(), weight, &config)? {
+            sync_neuron_permit(
+                &ctx.accounts.neuron,
+                &ctx.accounts.registry_authority,
+                &ctx.accounts.registry_program,
+                ctx.bumps.registry_authority,
+                false,
+            )?;
+        }
+
+        msg!("Unstaked {} SOL from validator {} in subnet {} (ticket {})", 
+             amount, ctx.accounts.validator.key(), stake_account.subnet_id, ticket.ticket_id);
 
         Ok(())
     }
 
-    /// Withdraw unstaked SOL after cooldown
+    /// Redeem a matured unstake ticket to its owner
     pub fn withdraw_unstaked(
         ctx: Context<WithdrawUnstaked>,
-        amount: u64,
     ) -> Result<()> {
-        let stake_account = &ctx.accounts.stake_account;
+        let stake_account = &mut ctx.accounts.stake_account;
+        let ticket = &ctx.accounts.ticket;
 
+        require!(ticket.asset == StakeAsset::Sol, StakingError::WrongStakeAsset);
         require!(
-            Clock::get()?.unix_timestamp >= stake_account.unstake_cooldown_until,
+            Clock::get()?.unix_timestamp >= ticket.unlock_at,
             StakingError::CooldownActive
         );
 
+        // Slashes while the ticket was pending reduce it
+        let amount = stake_account.redeem_ticket(ticket)?;
+
+        // Transfer SOL back
+        debit_stake_lamports(
+            &stake_account.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            amount,
+        )?;
+
+        msg!("Redeemed unstake ticket {} for {} SOL to {}", 
+             ticket.ticket_id, amount, ticket.owner);
+
+        Ok(())
+    }
+
+    /// Configure alpha staking and conversion weights for a subnet (governor only).
+    /// `alpha_mint` may be omitted for SOL-only subnets; alpha then carries no weight.
+    pub fn configure_subnet_staking(
+        ctx: Context<ConfigureSubnetStaking>,
+        subnet_id: u16,
+        sol_weight: u64,
+        alpha_weight: u64,
+        delegation_weight: u64,
+        max_delegation_ratio: u64,
+        min_permit_stake: u64,
+        min_self_stake: u64,
+        min_delegation: u64,
+    ) -> Result<()> {
+        require!(
+            subnet_governor(&ctx.accounts.subnet)? == ctx.accounts.governor.key(),
+            StakingError::Unauthorized
+        );
         require!(
-            stake_account.unstake_pending >= amount,
-            StakingError::InsufficientUnstakePending
+            sol_weight > 0
+                && sol_weight <= MAX_CONVERSION_WEIGHT
+                && alpha_weight <= MAX_CONVERSION_WEIGHT
+                && delegation_weight <= WEIGHT_DENOMINATOR
+                && max_delegation_ratio <= MAX_DELEGATION_RATIO,
+            StakingError::InvalidStakeWeightConfig
         );
 
-        // Transfer SOL back
-        **ctx.accounts.authority.to_account_info().try_borrow_mut_lamports()? += amount;
-        **ctx.accounts.stake_account.to_account_info().try_borrow_mut_lamports()? -= amount;
+        let config = &mut ctx.accounts.stake_config;
+
+        // The mint is fixed once set so existing alpha vaults stay valid
+        if let Some(alpha_mint) = &ctx.accounts.alpha_mint {
+            require!(
+                config.alpha_mint == Pubkey::default()
+                    || config.alpha_mint == alpha_mint.key(),
+                StakingError::WrongStakeAsset
+            );
+            config.alpha_mint = alpha_mint.key();
+        }
+        require!(
+            alpha_weight == 0 || config.alpha_mint != Pubkey::default(),
+            StakingError::InvalidStakeWeightConfig
+        );
 
-        stake_account.unstake_pending -= amount;
+        config.subnet_id = subnet_id;
+        config.sol_weight = sol_weight;
+        config.alpha_weight = alpha_weight;
+        config.delegation_weight = delegation_weight;
+        config.max_delegation_ratio = max_delegation_ratio;
+        config.min_permit_stake = min_permit_stake;
+        config.min_self_stake = min_self_stake;
+        config.min_delegation = min_delegation;
 
-        msg!("Withdrew {} SOL from unstake pool", amount);
+        msg!("Subnet {} staking configured: alpha mint {}, weights {}/{}/{}, delegation cap {}, permit threshold {}, minimums {}/{}", 
+             subnet_id, config.alpha_mint, sol_weight, alpha_weight, delegation_weight,
+             max_delegation_ratio, min_permit_stake, min_self_stake, min_delegation);
 
         Ok(())
     }
 
-    /// Calculate stake weight: W = α + 0.18 × τ
-    /// α = validator's direct stake, τ = delegated stake
-    pub fn calculate_stake_weight(
-        stake_account: &StakeAccount,
-    ) -> u64 {
-        let alpha = stake_account.amount - stake_account.delegated_amount;
-        let tau = stake_account.delegated_amount;
-        
-        // W = α + 0.18 × τ
-        // Using fixed-point math: 0.18 = 18/100
-        alpha + (tau * 18) / 100
+    /// Stake a subnet's alpha token as validator self-stake
+    pub fn stake_alpha(
+        ctx: Context<StakeAlpha>,
+        subnet_id: u16,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        require_keys_eq!(
+            config.alpha_mint,
+            ctx.accounts.alpha_mint.key(),
+            StakingError::WrongStakeAsset
+        );
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.validator.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            subnet_id,
+            ctx.accounts.validator.key(),
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
+
+        // Transfer alpha to the stake account's vault
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.validator_token.to_account_info(),
+                    to: ctx.accounts.alpha_vault.to_account_info(),
+                    authority: ctx.accounts.validator.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.alpha_mint = ctx.accounts.alpha_mint.key();
+        stake_account.alpha_amount += amount;
+
+        require!(
+            meets_minimum(stake_account.alpha_amount, config.min_self_stake),
+            StakingError::BelowMinimumStake
+        );
+
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            calculate_stake_weight(stake_account, &config)?,
+        )?;
+
+        msg!("Validator {} staked {} alpha in subnet {}", 
+             ctx.accounts.validator.key(), amount, subnet_id);
+
+        Ok(())
     }
 
-    /// Update validator permit eligibility based on stake and emissions
-    pub fn update_permit_eligibility(
-        _ctx: Context<UpdatePermitEligibility>,
+    /// Unstake alpha into a per-request ticket (with cooldown period)
+    pub fn unstake_alpha(
+        ctx: Context<UnstakeAlpha>,
+        amount: u64,
     ) -> Result<()> {
-        // This would typically be called by consensus program after epoch finalization
-        // Stake weight calculation is done via calculate_stake_weight()
-        // Actual permit assignment happens in consensus program
-        // This function is called by consensus program to update eligibility
+        require!(amount > 0, StakingError::InvalidAmount);
+
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.validator.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.stake_account.subnet_id,
+            ctx.accounts.validator.key(),
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
+
+        let stake_account = &mut ctx.accounts.stake_account;
+
+        require!(
+            stake_account.alpha_amount >= amount,
+            StakingError::InsufficientStake
+        );
+        stake_account.alpha_amount -= amount;
+        require!(
+            meets_minimum(stake_account.alpha_amount, config.min_self_stake),
+            StakingError::BelowMinimumStake
+        );
+
+        let ticket = &mut ctx.accounts.ticket;
+        ticket.owner = ctx.accounts.validator.key();
+        ticket.stake_account = stake_account.key();
+        ticket.unlock_at = Clock::get()?.unix_timestamp + UNSTAKE_COOLDOWN;
+        stake_account.open_ticket(ticket, StakeAsset::Alpha, amount)?;
+
+        let weight = calculate_stake_weight(stake_account, &config)?;
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            weight,
+        )?;
+
+        if refresh_permit(stake_account, ctx.accounts.validator_set.as_mut(), weight, &config)? {
+            sync_neuron_permit(
+                &ctx.accounts.neuron,
+                &ctx.accounts.registry_authority,
+                &ctx.accounts.registry_program,
+                ctx.bumps.registry_authority,
+                false,
+            )?;
+        }
+
+        msg!("Unstaked {} alpha from validator {} in subnet {} (ticket {})", 
+             amount, stake_account.validator, stake_account.subnet_id, ticket.ticket_id);
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(subnet_id: u16)]
-pub struct StakeValidator<'info> {
-    #[account(
-        init,
-        payer = validator,
-        space = 8 + StakeAccount::LEN,
-        seeds = [b"stake", validator.key().as_ref(), &subnet_id.to_le_bytes()],
-        bump
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
-    
-    #[account(mut)]
-    pub validator: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-}
+    /// Redeem a matured alpha unstake ticket to its owner
+    pub fn withdraw_unstaked_alpha(
+        ctx: Context<WithdrawUnstakedAlpha>,
+    ) -> Result<()> {
+        let stake_account = &mut ctx.accounts.stake_account;
+        let ticket = &ctx.accounts.ticket;
 
-#[derive(Accounts)]
-#[instruction(subnet_id: u16)]
-pub struct Delegate<'info> {
-    #[account(
-        mut,
-        seeds = [b"stake", validator.key().as_ref(), &subnet_id.to_le_bytes()],
-        bump
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
-    
-    #[account(
-        init_if_needed,
-        payer = delegator,
-        space = 8 + Delegation::LEN,
-        seeds = [b"delegation", delegator.key().as_ref(), validator.key().as_ref(), &subnet_id.to_le_bytes()],
-        bump
-    )]
-    pub delegation: Account<'info, Delegation>,
-    
-    /// CHECK: Validator
-    pub validator: UncheckedAccount<'info>,
-    
-    #[account(mut)]
-    pub delegator: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-}
+        require!(ticket.asset == StakeAsset::Alpha, StakingError::WrongStakeAsset);
+        require!(
+            Clock::get()?.unix_timestamp >= ticket.unlock_at,
+            StakingError::CooldownActive
+        );
 
-#[derive(Accounts)]
-pub struct Unstake<'info> {
-    #[account(
-        mut,
-        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
-        bump
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
-    
-    /// CHECK: Validator or delegator
-    pub authority: Signer<'info>,
-    
-    /// CHECK: Validator
-    pub validator: UncheckedAccount<'info>,
-    
-    /// CHECK: Optional delegation account
-    pub delegation: Option<Account<'info, Delegation>>,
-    
-    pub system_program: Program<'info, System>,
-}
+        // Slashes while the ticket was pending reduce it
+        let amount = stake_account.redeem_ticket(ticket)?;
 
-#[derive(Accounts)]
-pub struct WithdrawUnstaked<'info> {
-    #[account(
-        mut,
-        seeds = [b"stake", validator.key().as_ref(), neuron.subnet_id.to_le_bytes().as_ref()],
-        bump,
-        has_one = authority @ StakingError::Unauthorized
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
-    
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-}
+        // The stake account PDA owns the vault
+        let subnet_bytes = stake_account.subnet_id.to_le_bytes();
+        let signer_seeds: &[&[u8]] = &[
+            b"stake",
+            stake_account.validator.as_ref(),
+            &subnet_bytes,
+            &[ctx.bumps.stake_account],
+        ];
 
-#[derive(Accounts)]
-pub struct UpdatePermitEligibility<'info> {
-    #[account(
-        mut,
-        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
-        bump
-    )]
-    pub stake_account: Account<'info, StakeAccount>,
-    
-    /// CHECK: Authority (consensus program)
-    pub authority: Signer<'info>,
-    
-    /// CHECK: Validator
-    pub validator: UncheckedAccount<'info>,
-}
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.alpha_vault.to_account_info(),
+                    to: ctx.accounts.owner_token.to_account_info(),
+                    authority: stake_account.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            amount,
+        )?;
 
-#[account]
-pub struct StakeAccount {
-    pub validator: Pubkey,
-    pub subnet_id: u16,
-    pub amount: u64,
-    pub delegated_amount: u64,
-    pub unstake_pending: u64,
-    pub unstake_cooldown_until: i64,
-}
+        msg!("Redeemed alpha unstake ticket {} for {} to {}", 
+             ticket.ticket_id, amount, ticket.owner);
 
-impl StakeAccount {
-    pub const LEN: usize = 32 + 2 + 8 + 8 + 8 + 8;
-}
+        Ok(())
+    }
 
-#[account]
-pub struct Delegation {
-    pub delegator: Pubkey,
-    pub validator: Pubkey,
-    pub subnet_id: u16,
-    pub amount: u64,
-}
+    /// Move delegated stake to another validator in the same subnet without a cooldown
+    pub fn redelegate(
+        ctx: Context<Redelegate>,
+        subnet_id: u16,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
 
-impl Delegation {
-    pub const LEN: usize = 32 + 32 + 2 + 8;
-}
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        for (stake_account, validator) in [
+            (&ctx.accounts.source_stake_account, ctx.accounts.source_validator.key()),
+            (&ctx.accounts.dest_stake_account, ctx.accounts.dest_validator.key()),
+        ] {
+            record_stake_snapshot(
+                &ctx.accounts.stake_snapshot,
+                &ctx.accounts.delegator.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                subnet_id,
+                validator,
+                calculate_stake_weight(stake_account, &config)?,
+            )?;
+        }
 
-#[error_code]
-pub enum StakingError {
-    #[msg("Invalid amount")]
-    InvalidAmount,
-    #[msg("Insufficient stake")]
-    InsufficientStake,
+        let now = Clock::get()?.unix_timestamp;
+        let source_stake = &mut ctx.accounts.source_stake_account;
+        let dest_stake = &mut ctx.accounts.dest_stake_account;
+        let source = &mut ctx.accounts.source_delegation;
+        let dest = &mut ctx.accounts.dest_delegation;
+
+        require!(
+            now >= source.last_redelegated_at + REDELEGATE_INTERVAL,
+            StakingError::RedelegationTooSoon
+        );
+
+        // Settle both sides before their amounts change
+        source.apply_slash(source_stake.delegation_scale)?;
+        require!(source.amount >= amount, StakingError::InsufficientStake);
+        require!(!source.is_locked(now), StakingError::DelegationLocked);
+        let source_lock = source.lock_entry();
+        source.release_expired_lock(now);
+        source.settle_rewards(source_stake.reward_per_share)?;
+        source.amount -= amount;
+        source.reset_reward_debt(source_stake.reward_per_share)?;
+        source.last_redelegated_at = now;
+        source_stake.update_delegation_bonus(source_lock, source.lock_entry(), now)?;
+
+        if dest.delegator == Pubkey::default() {
+            // New delegation
+            dest.delegator = ctx.accounts.delegator.key();
+            dest.validator = ctx.accounts.dest_validator.key();
+            dest.subnet_id = subnet_id;
+            dest_stake.delegation_count += 1;
+        }
+
+        // Moved stake is not locked at the destination
+        dest.apply_slash(dest_stake.delegation_scale)?;
+        let dest_lock = dest.lock_entry();
+        dest.release_expired_lock(now);
+        dest.settle_rewards(dest_stake.reward_per_share)?;
+        dest.amount += amount;
+        dest.reset_reward_debt(dest_stake.reward_per_share)?;
+        dest.last_redelegated_at = now;
+        dest_stake.update_delegation_bonus(dest_lock, dest.lock_entry(), now)?;
+
+        require!(
+            meets_minimum(source.amount, config.min_delegation)
+                && meets_minimum(dest.amount, config.min_delegation),
+            StakingError::BelowMinimumStake
+        );
+
+        source_stake.amount -= amount;
+        source_stake.delegated_amount = source_stake.delegated_amount.saturating_sub(amount);
+        dest_stake.amount += amount;
+        dest_stake.delegated_amount += amount;
+
+        debit_stake_lamports(
+            &source_stake.to_account_info(),
+            &dest_stake.to_account_info(),
+            amount,
+        )?;
+
+        let source_weight = calculate_stake_weight(source_stake, &config)?;
+        sync_neuron_stake(
+            &ctx.accounts.source_neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            source_weight,
+        )?;
+        if refresh_permit(source_stake, ctx.accounts.validator_set.as_mut(), source_weight, &config)? {
+            sync_neuron_permit(
+                &ctx.accounts.source_neuron,
+                &ctx.accounts.registry_authority,
+                &ctx.accounts.registry_program,
+                ctx.bumps.registry_authority,
+                false,
+            )?;
+        }
+        sync_neuron_stake(
+            &ctx.accounts.dest_neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            calculate_stake_weight(dest_stake, &config)?,
+        )?;
+
+        msg!("Delegator {} redelegated {} SOL from validator {} to {} in subnet {}", 
+             ctx.accounts.delegator.key(), amount, ctx.accounts.source_validator.key(),
+             ctx.accounts.dest_validator.key(), subnet_id);
+
+        Ok(())
+    }
+
+    /// Lock a delegation for one of `DELEGATION_LOCK_TIERS` in exchange for a
+    /// stake weight multiplier on its current amount; later top-ups are not
+    /// covered until the delegation is locked again. Locks run to the end of
+    /// the day the term ends and can be extended but never shortened.
+    pub fn lock_delegation(
+        ctx: Context<LockDelegation>,
+        lock_duration: i64,
+    ) -> Result<()> {
+        let (_, multiplier_bps) = DELEGATION_LOCK_TIERS
+            .iter()
+            .find(|(duration, _)| *duration == lock_duration)
+            .copied()
+            .ok_or(StakingError::InvalidLockDuration)?;
+
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.delegator.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.stake_account.subnet_id,
+            ctx.accounts.stake_account.validator,
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let stake_account = &mut ctx.accounts.stake_account;
+        let delegation = &mut ctx.accounts.delegation;
+
+        delegation.apply_slash(stake_account.delegation_scale)?;
+        require!(delegation.amount > 0, StakingError::InsufficientStake);
+
+        let old_lock = delegation.lock_entry();
+        delegation.release_expired_lock(now);
+
+        let locked_until = lock_expiry(now + lock_duration);
+        require!(
+            locked_until >= delegation.locked_until
+                && multiplier_bps >= delegation.lock_multiplier_bps,
+            StakingError::InvalidLockDuration
+        );
+        delegation.locked_until = locked_until;
+        delegation.lock_multiplier_bps = multiplier_bps;
+        delegation.locked_amount = delegation.amount;
+
+        stake_account.update_delegation_bonus(old_lock, delegation.lock_entry(), now)?;
+
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            calculate_stake_weight(stake_account, &config)?,
+        )?;
+
+        msg!("Delegator {} locked {} SOL with validator {} until {} ({} bps)", 
+             delegation.delegator, delegation.amount, delegation.validator, locked_until,
+             multiplier_bps);
+
+        Ok(())
+    }
+
+    /// Clear the multiplier of a delegation whose lock has run out
+    /// (permissionless). Stake weight already stops counting the bonus at
+    /// expiry; this only tidies the delegation and the validator's schedule.
+    pub fn expire_delegation_lock(
+        ctx: Context<ExpireDelegationLock>,
+    ) -> Result<()> {
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.stake_account.subnet_id,
+            ctx.accounts.stake_account.validator,
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let stake_account = &mut ctx.accounts.stake_account;
+        let delegation = &mut ctx.accounts.delegation;
+
+        require!(
+            delegation.lock_multiplier_bps != 0 && !delegation.is_locked(now),
+            StakingError::DelegationLocked
+        );
+
+        delegation.apply_slash(stake_account.delegation_scale)?;
+        let old_lock = delegation.lock_entry();
+        delegation.release_expired_lock(now);
+        stake_account.update_delegation_bonus(old_lock, delegation.lock_entry(), now)?;
+
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            calculate_stake_weight(stake_account, &config)?,
+        )?;
+
+        msg!("Delegation lock of {} with validator {} expired", 
+             delegation.delegator, delegation.validator);
+
+        Ok(())
+    }
+
+    /// Record a validator's current weight in this epoch's snapshot (permissionless)
+    pub fn snapshot_stake(
+        ctx: Context<SnapshotStake>,
+    ) -> Result<()> {
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.stake_account.subnet_id,
+            ctx.accounts.stake_account.validator,
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Close a stake snapshot once it is `SNAPSHOT_RETENTION_EPOCHS` old,
+    /// refunding its rent to whoever created it (permissionless)
+    pub fn close_stake_snapshot(
+        ctx: Context<CloseStakeSnapshot>,
+        subnet_id: u16,
+        epoch: u64,
+    ) -> Result<()> {
+        let snapshot = &ctx.accounts.stake_snapshot;
+        require!(
+            snapshot.epoch + SNAPSHOT_RETENTION_EPOCHS < epoch_at(Clock::get()?.unix_timestamp),
+            StakingError::SnapshotRetained
+        );
+
+        msg!("Closed stake snapshot for epoch {} in subnet {}", epoch, subnet_id);
+
+        Ok(())
+    }
+
+    /// Schedule a commission change, effective after `COMMISSION_CHANGE_DELAY`
+    pub fn set_commission(
+        ctx: Context<SetCommission>,
+        commission_bps: u16,
+    ) -> Result<()> {
+        require!(
+            commission_bps <= MAX_COMMISSION_BPS,
+            StakingError::InvalidCommission
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.apply_pending_commission(now);
+
+        stake_account.pending_commission_bps = commission_bps;
+        stake_account.commission_effective_at = now + COMMISSION_CHANGE_DELAY;
+
+        msg!("Validator {} commission set to {} bps from {}", 
+             stake_account.validator, commission_bps, stake_account.commission_effective_at);
+
+        Ok(())
+    }
+
+    /// Opt a validator's rewards in or out of auto-compounding
+    pub fn set_auto_compound(
+        ctx: Context<SetAutoCompound>,
+        enabled: bool,
+    ) -> Result<()> {
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.auto_compound = enabled;
+
+        msg!("Validator {} auto-compound: {}", stake_account.validator, enabled);
+
+        Ok(())
+    }
+
+    /// Opt a delegation's rewards in or out of auto-compounding
+    pub fn set_delegation_auto_compound(
+        ctx: Context<SetDelegationAutoCompound>,
+        enabled: bool,
+    ) -> Result<()> {
+        let delegation = &mut ctx.accounts.delegation;
+        delegation.auto_compound = enabled;
+
+        msg!("Delegator {} auto-compound with validator {}: {}", 
+             delegation.delegator, delegation.validator, enabled);
+
+        Ok(())
+    }
+
+    /// Credit rewards to a validator, sharing the delegated portion with delegators
+    pub fn credit_rewards(
+        ctx: Context<CreditRewards>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+
+        // Transfer rewards to stake account
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.funder.to_account_info(),
+                    to: ctx.accounts.stake_account.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.apply_pending_commission(Clock::get()?.unix_timestamp);
+
+        // Delegators earn pro-rata to their share of total stake, less commission
+        let delegator_rewards = if stake_account.delegated_amount > 0 && stake_account.amount > 0 {
+            let delegated_share = amount as u128 * stake_account.delegated_amount as u128
+                / stake_account.amount as u128;
+            let commission = delegated_share * stake_account.commission_bps as u128 / 10000;
+            (delegated_share - commission) as u64
+        } else {
+            0
+        };
+
+        if delegator_rewards > 0 {
+            stake_account.reward_per_share = stake_account
+                .reward_per_share
+                .checked_add(
+                    delegator_rewards as u128 * stake_account.delegation_scale
+                        / stake_account.delegated_amount as u128,
+                )
+                .ok_or(StakingError::MathOverflow)?;
+        }
+
+        stake_account.validator_rewards = stake_account
+            .validator_rewards
+            .checked_add(amount - delegator_rewards)
+            .ok_or(StakingError::MathOverflow)?;
+
+        msg!("Credited {} to validator {} ({} to delegators)", 
+             amount, stake_account.validator, delegator_rewards);
+
+        Ok(())
+    }
+
+    /// Claim a delegator's accrued share of validator rewards
+    pub fn claim_delegator_rewards(
+        ctx: Context<ClaimDelegatorRewards>,
+    ) -> Result<()> {
+        let stake_account = &ctx.accounts.stake_account;
+        let delegation = &mut ctx.accounts.delegation;
+
+        delegation.settle_rewards(stake_account.reward_per_share)?;
+        delegation.reset_reward_debt(stake_account.reward_per_share)?;
+
+        let amount = delegation.pending_rewards;
+        require!(amount > 0, StakingError::NoRewardsToClaim);
+        delegation.pending_rewards = 0;
+
+        debit_stake_lamports(
+            &stake_account.to_account_info(),
+            &ctx.accounts.delegator.to_account_info(),
+            amount,
+        )?;
+
+        msg!("Delegator {} claimed {} from validator {}", 
+             ctx.accounts.delegator.key(), amount, stake_account.validator);
+
+        Ok(())
+    }
+
+    /// Claim a validator's commission and self-stake rewards
+    pub fn claim_validator_rewards(
+        ctx: Context<ClaimValidatorRewards>,
+    ) -> Result<()> {
+        let stake_account = &mut ctx.accounts.stake_account;
+
+        let amount = stake_account.validator_rewards;
+        require!(amount > 0, StakingError::NoRewardsToClaim);
+        stake_account.validator_rewards = 0;
+
+        debit_stake_lamports(
+            &stake_account.to_account_info(),
+            &ctx.accounts.validator.to_account_info(),
+            amount,
+        )?;
+
+        msg!("Validator {} claimed {} in rewards", stake_account.validator, amount);
+
+        Ok(())
+    }
+
+    /// Restake `owner`'s accrued rewards instead of paying them out, when they
+    /// claim through the emissions program (emissions program only). The
+    /// validator's rewards compound into self-stake and the delegation's into
+    /// its amount, each only when its owner is `owner` and opted in.
+    pub fn compound_rewards(
+        ctx: Context<CompoundRewards>,
+        owner: Pubkey,
+    ) -> Result<()> {
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        record_stake_snapshot(
+            &ctx.accounts.stake_snapshot,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.stake_account.subnet_id,
+            ctx.accounts.stake_account.validator,
+            calculate_stake_weight(&ctx.accounts.stake_account, &config)?,
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let stake_account = &mut ctx.accounts.stake_account;
+
+        // Rewards are already held by the stake account, so only the books move
+        let mut validator_amount = 0;
+        if stake_account.validator == owner && stake_account.auto_compound {
+            validator_amount = stake_account.validator_rewards;
+            stake_account.validator_rewards = 0;
+            stake_account.amount = stake_account
+                .amount
+                .checked_add(validator_amount)
+                .ok_or(StakingError::MathOverflow)?;
+        }
+
+        let mut delegator = None;
+        let mut delegator_amount = 0;
+        if let Some(delegation) = &mut ctx.accounts.delegation {
+            if delegation.delegator == owner && delegation.auto_compound {
+                delegation.apply_slash(stake_account.delegation_scale)?;
+                let old_lock = delegation.lock_entry();
+                delegation.release_expired_lock(now);
+                delegation.settle_rewards(stake_account.reward_per_share)?;
+
+                delegator_amount = delegation.pending_rewards;
+                delegation.pending_rewards = 0;
+                delegation.amount = delegation
+                    .amount
+                    .checked_add(delegator_amount)
+                    .ok_or(StakingError::MathOverflow)?;
+                delegation.reset_reward_debt(stake_account.reward_per_share)?;
+                stake_account.update_delegation_bonus(old_lock, delegation.lock_entry(), now)?;
+
+                stake_account.amount = stake_account
+                    .amount
+                    .checked_add(delegator_amount)
+                    .ok_or(StakingError::MathOverflow)?;
+                stake_account.delegated_amount += delegator_amount;
+                delegator = Some(delegation.delegator);
+            }
+        }
+
+        require!(
+            validator_amount > 0 || delegator_amount > 0,
+            StakingError::NoRewardsToClaim
+        );
+
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            calculate_stake_weight(stake_account, &config)?,
+        )?;
+
+        emit!(RewardsCompounded {
+            validator: stake_account.validator,
+            subnet_id: stake_account.subnet_id,
+            delegator,
+            validator_amount,
+            delegator_amount,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Close an emptied delegation and refund its rent to the delegator
+    pub fn close_delegation(
+        ctx: Context<CloseDelegation>,
+    ) -> Result<()> {
+        let stake_account = &mut ctx.accounts.stake_account;
+        let delegation = &mut ctx.accounts.delegation;
+
+        delegation.apply_slash(stake_account.delegation_scale)?;
+        delegation.settle_rewards(stake_account.reward_per_share)?;
+        require!(
+            delegation.amount == 0 && delegation.pending_rewards == 0,
+            StakingError::AccountNotEmpty
+        );
+
+        stake_account.remove_delegation()?;
+
+        msg!("Delegator {} closed delegation to validator {} in subnet {}", 
+             delegation.delegator, delegation.validator, delegation.subnet_id);
+
+        Ok(())
+    }
+
+    /// Close an emptied validator stake account (and its alpha vault) and
+    /// refund rent to the validator. All delegations must be closed first.
+    pub fn close_stake_account(
+        ctx: Context<CloseStakeAccount>,
+    ) -> Result<()> {
+        let stake_account = &ctx.accounts.stake_account;
+
+        require!(stake_account.is_empty(), StakingError::AccountNotEmpty);
+
+        if stake_account.alpha_mint != Pubkey::default() {
+            // Pending alpha tickets are still held in the vault
+            let alpha_vault = ctx
+                .accounts
+                .alpha_vault
+                .as_ref()
+                .ok_or(StakingError::AccountNotEmpty)?;
+            require!(alpha_vault.amount == 0, StakingError::AccountNotEmpty);
+
+            let subnet_bytes = stake_account.subnet_id.to_le_bytes();
+            let signer_seeds: &[&[u8]] = &[
+                b"stake",
+                stake_account.validator.as_ref(),
+                &subnet_bytes,
+                &[ctx.bumps.stake_account],
+            ];
+
+            token::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::CloseAccount {
+                    account: alpha_vault.to_account_info(),
+                    destination: ctx.accounts.validator.to_account_info(),
+                    authority: stake_account.to_account_info(),
+                },
+                &[signer_seeds],
+            ))?;
+        }
+
+        msg!("Validator {} closed stake account in subnet {}", 
+             stake_account.validator, stake_account.subnet_id);
+
+        Ok(())
+    }
+
+    /// Slash a validator's self, delegated and alpha stake, including stake
+    /// waiting in unstake tickets (consensus program only). Alpha is moved to
+    /// the treasury's alpha token account, so `alpha_vault`, `treasury_alpha`
+    /// and `token_program` are required if the validator holds any alpha.
+    pub fn slash(
+        ctx: Context<Slash>,
+        slash_bps: u16,
+        reason: SlashReason,
+    ) -> Result<()> {
+        require!(
+            slash_bps > 0 && slash_bps <= MAX_SLASH_BPS,
+            StakingError::InvalidSlashFraction
+        );
+
+        let stake_account = &mut ctx.accounts.stake_account;
+        let SlashedStake {
+            self_slashed,
+            delegated_slashed,
+            pending_slashed,
+            alpha_slashed,
+            alpha_pending_slashed,
+        } = stake_account.slash_stake(slash_bps)?;
+        let total_slashed = self_slashed + delegated_slashed + pending_slashed;
+
+        debit_stake_lamports(
+            &stake_account.to_account_info(),
+            &ctx.accounts.treasury.to_account_info(),
+            total_slashed,
+        )?;
+
+        let alpha_total = alpha_slashed + alpha_pending_slashed;
+        if alpha_total > 0 {
+            let (Some(alpha_vault), Some(treasury_alpha), Some(token_program)) = (
+                &ctx.accounts.alpha_vault,
+                &ctx.accounts.treasury_alpha,
+                &ctx.accounts.token_program,
+            ) else {
+                return err!(StakingError::WrongStakeAsset);
+            };
+            require_keys_eq!(treasury_alpha.mint, alpha_vault.mint, StakingError::WrongStakeAsset);
+
+            // The stake account PDA owns the vault
+            let subnet_bytes = stake_account.subnet_id.to_le_bytes();
+            let signer_seeds: &[&[u8]] = &[
+                b"stake",
+                stake_account.validator.as_ref(),
+                &subnet_bytes,
+                &[ctx.bumps.stake_account],
+            ];
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    token::Transfer {
+                        from: alpha_vault.to_account_info(),
+                        to: treasury_alpha.to_account_info(),
+                        authority: stake_account.to_account_info(),
+                    },
+                    &[signer_seeds],
+                ),
+                alpha_total,
+            )?;
+        }
+
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        let weight = calculate_stake_weight(stake_account, &config)?;
+        sync_neuron_stake(
+            &ctx.accounts.neuron,
+            &ctx.accounts.registry_authority,
+            &ctx.accounts.registry_program,
+            ctx.bumps.registry_authority,
+            weight,
+        )?;
+
+        if refresh_permit(stake_account, ctx.accounts.validator_set.as_mut(), weight, &config)? {
+            sync_neuron_permit(
+                &ctx.accounts.neuron,
+                &ctx.accounts.registry_authority,
+                &ctx.accounts.registry_program,
+                ctx.bumps.registry_authority,
+                false,
+            )?;
+        }
+
+        emit!(StakeSlashed {
+            validator: stake_account.validator,
+            subnet_id: stake_account.subnet_id,
+            slash_bps,
+            reason,
+            self_slashed,
+            delegated_slashed,
+            pending_slashed,
+            alpha_slashed: alpha_total,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Slashed {} SOL and {} alpha from validator {} in subnet {}", 
+             total_slashed, alpha_total, stake_account.validator, stake_account.subnet_id);
+
+        Ok(())
+    }
+
+    /// Create the treasury that receives slashed stake. Only the program's
+    /// upgrade authority can create it and name who governs withdrawals.
+    pub fn initialize_treasury(
+        ctx: Context<InitializeTreasury>,
+        authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.treasury.authority = authority;
+
+        msg!("Treasury initialized with authority {}", authority);
+
+        Ok(())
+    }
+
+    /// Withdraw slashed SOL from the treasury (treasury authority only)
+    pub fn withdraw_treasury(
+        ctx: Context<WithdrawTreasury>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+
+        debit_stake_lamports(
+            &ctx.accounts.treasury.to_account_info(),
+            &ctx.accounts.recipient.to_account_info(),
+            amount,
+        )?;
+
+        msg!("Withdrew {} SOL from the treasury to {}", amount, ctx.accounts.recipient.key());
+
+        Ok(())
+    }
+
+    /// Withdraw slashed alpha from a treasury token account (treasury authority only)
+    pub fn withdraw_treasury_alpha(
+        ctx: Context<WithdrawTreasuryAlpha>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, StakingError::InvalidAmount);
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.treasury_alpha.to_account_info(),
+                    to: ctx.accounts.recipient_token.to_account_info(),
+                    authority: ctx.accounts.treasury.to_account_info(),
+                },
+                &[&[b"treasury", &[ctx.bumps.treasury]]],
+            ),
+            amount,
+        )?;
+
+        msg!("Withdrew {} alpha from the treasury to {}", amount, ctx.accounts.recipient_token.key());
+
+        Ok(())
+    }
+
+    /// Governance voting power of `owner` in a subnet, returned via return data.
+    /// Counts the owner's own validator stake (if `stake_account` is passed) and
+    /// each delegation passed in `remaining_accounts` as (delegation, validator
+    /// stake account) pairs. Delegations still under lock count with their
+    /// multiplier. Other programs read it with `cpi::voting_power`.
+    pub fn voting_power(
+        ctx: Context<GetVotingPower>,
+        owner: Pubkey,
+        subnet_id: u16,
+    ) -> Result<VotingPower> {
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        let now = Clock::get()?.unix_timestamp;
+
+        let self_stake = match &ctx.accounts.stake_account {
+            Some(stake_account) => u64::try_from(self_stake_weight(stake_account, &config)?)
+                .map_err(|_| StakingError::MathOverflow)?,
+            None => 0,
+        };
+
+        require!(
+            ctx.remaining_accounts.len() % 2 == 0,
+            StakingError::InvalidVotingAccounts
+        );
+
+        let mut counted: Vec<Pubkey> = Vec::new();
+        let mut delegated: u128 = 0;
+        for pair in ctx.remaining_accounts.chunks(2) {
+            require!(
+                pair[0].owner == &crate::ID && pair[1].owner == &crate::ID,
+                StakingError::InvalidVotingAccounts
+            );
+            let mut delegation = Delegation::try_deserialize(&mut pair[0].try_borrow_data()?.as_ref())?;
+            let stake_account = StakeAccount::try_deserialize(&mut pair[1].try_borrow_data()?.as_ref())?;
+
+            require!(
+                delegation.delegator == owner
+                    && delegation.subnet_id == subnet_id
+                    && delegation.validator == stake_account.validator
+                    && stake_account.subnet_id == subnet_id
+                    && !counted.contains(pair[0].key),
+                StakingError::InvalidVotingAccounts
+            );
+            counted.push(*pair[0].key);
+
+            delegation.apply_slash(stake_account.delegation_scale)?;
+            let mut amount = delegation.amount as u128;
+            if delegation.is_locked(now) {
+                amount += delegation.lock_bonus() as u128;
+            }
+
+            delegated = amount
+                .checked_mul(config.sol_weight as u128)
+                .map(|a| a / WEIGHT_DENOMINATOR as u128)
+                .and_then(|a| delegated.checked_add(a))
+                .ok_or(StakingError::MathOverflow)?;
+        }
+
+        let delegated_stake = u64::try_from(delegated).map_err(|_| StakingError::MathOverflow)?;
+        let total = self_stake
+            .checked_add(delegated_stake)
+            .ok_or(StakingError::MathOverflow)?;
+
+        msg!("Voting power of {} in subnet {}: {}", owner, subnet_id, total);
+
+        Ok(VotingPower {
+            owner,
+            subnet_id,
+            self_stake,
+            delegated_stake,
+            total,
+        })
+    }
+
+    /// Re-evaluate a validator's permit against the subnet threshold and
+    /// validator set. Permissionless: the outcome depends only on stake.
+    /// When the set is full, the validator displaces the lowest-weight holder
+    /// if it outweighs them, and that holder's accounts must be passed in.
+    /// The holder's weight is recomputed from its stake account first; other
+    /// entries are refreshed whenever their holder unstakes or is re-evaluated.
+    pub fn update_permit_eligibility(
+        ctx: Context<UpdatePermitEligibility>,
+    ) -> Result<()> {
+        let subnet_id = ctx.accounts.stake_account.subnet_id;
+        let validator = ctx.accounts.stake_account.validator;
+        let config = load_stake_config(&ctx.accounts.stake_config)?;
+        let weight = calculate_stake_weight(&ctx.accounts.stake_account, &config)?;
+        let limit = (subnet_validator_limit(&ctx.accounts.subnet)? as usize)
+            .min(MAX_VALIDATOR_PERMITS);
+        let epoch = epoch_at(Clock::get()?.unix_timestamp);
+
+        let validator_set = &mut ctx.accounts.validator_set;
+        validator_set.subnet_id = subnet_id;
+        validator_set.validators.retain(|e| e.validator != validator);
+
+        let mut eligible = weight > 0 && weight >= config.min_permit_stake;
+        let mut evicted = None;
+
+        if eligible && validator_set.validators.len() >= limit {
+            // Set is full: take the lowest holder's slot only by outweighing
+            // its current weight
+            let evicted_stake = ctx
+                .accounts
+                .evicted_stake_account
+                .as_ref()
+                .ok_or(StakingError::MissingEvictedValidator)?;
+            require!(evicted_stake.subnet_id == subnet_id, StakingError::MissingEvictedValidator);
+            let index = validator_set
+                .validators
+                .iter()
+                .position(|e| e.validator == evicted_stake.validator)
+                .ok_or(StakingError::MissingEvictedValidator)?;
+
+            let lowest_weight = calculate_stake_weight(evicted_stake, &config)?;
+            validator_set.validators[index].weight = lowest_weight;
+            require!(
+                validator_set.validators.iter().all(|e| e.weight >= lowest_weight),
+                StakingError::MissingEvictedValidator
+            );
+
+            if weight > lowest_weight {
+                validator_set.validators.remove(index);
+                evicted = Some(evicted_stake.validator);
+            } else {
+                eligible = false;
+            }
+        }
+
+        if eligible {
+            validator_set.validators.push(SnapshotEntry { validator, weight });
+        }
+
+        if let Some(evicted_validator) = evicted {
+            let evicted_stake = ctx
+                .accounts
+                .evicted_stake_account
+                .as_mut()
+                .ok_or(StakingError::MissingEvictedValidator)?;
+
+            let evicted_neuron = ctx
+                .accounts
+                .evicted_neuron
+                .as_ref()
+                .ok_or(StakingError::MissingEvictedValidator)?;
+            let (expected_neuron, _bump) = Pubkey::find_program_address(
+                &[b"neuron", &subnet_id.to_le_bytes(), evicted_validator.as_ref()],
+                &REGISTRY_PROGRAM_ID,
+            );
+            require_keys_eq!(
+                evicted_neuron.key(),
+                expected_neuron,
+                StakingError::MissingEvictedValidator
+            );
+
+            evicted_stake.has_permit = false;
+            evicted_stake.permit_changed_epoch = epoch;
+
+            sync_neuron_permit(
+                evicted_neuron,
+                &ctx.accounts.registry_authority,
+                &ctx.accounts.registry_program,
+                ctx.bumps.registry_authority,
+                false,
+            )?;
+
+            msg!("Validator {} lost its permit in subnet {}", evicted_validator, subnet_id);
+        }
+
+        let stake_account = &mut ctx.accounts.stake_account;
+        if stake_account.has_permit != eligible {
+            stake_account.has_permit = eligible;
+            stake_account.permit_changed_epoch = epoch;
+
+            sync_neuron_permit(
+                &ctx.accounts.neuron,
+                &ctx.accounts.registry_authority,
+                &ctx.accounts.registry_program,
+                ctx.bumps.registry_authority,
+                eligible,
+            )?;
+        }
+
+        msg!("Validator {} permit in subnet {}: {} (weight {})", 
+             validator, subnet_id, eligible, weight);
+
+        Ok(())
+    }
+}
+
+/// Calculate stake weight: W = α + c × min(τ, r × α)
+/// α = validator's direct stake (SOL and alpha, converted by the subnet's
+/// weights), τ = delegated stake plus the bonus from time-locked delegations
+/// whose locks have not yet expired,
+/// c = delegation weight (0.18 by default),
+/// r = optional cap on delegated stake relative to self-stake
+pub fn calculate_stake_weight(
+    stake_account: &StakeAccount,
+    config: &SubnetStakeConfig,
+) -> Result<u64> {
+    stake_weight_at(stake_account, config, Clock::get()?.unix_timestamp)
+}
+
+/// Stake weight as `calculate_stake_weight`, counting lock bonuses active at `now`
+pub fn stake_weight_at(
+    stake_account: &StakeAccount,
+    config: &SubnetStakeConfig,
+    now: i64,
+) -> Result<u64> {
+    let denominator = WEIGHT_DENOMINATOR as u128;
+    let alpha = self_stake_weight(stake_account, config)?;
+    let lock_bonus = stake_account.active_lock_bonus(now);
+
+    let mut tau = (stake_account.delegated_amount as u128 + lock_bonus as u128)
+        .checked_mul(config.sol_weight as u128)
+        .ok_or(StakingError::MathOverflow)?
+        / denominator;
+
+    if config.max_delegation_ratio > 0 {
+        let cap = alpha
+            .checked_mul(config.max_delegation_ratio as u128)
+            .ok_or(StakingError::MathOverflow)?
+            / denominator;
+        tau = tau.min(cap);
+    }
+
+    let weight = tau
+        .checked_mul(config.delegation_weight as u128)
+        .map(|t| t / denominator)
+        .and_then(|t| alpha.checked_add(t))
+        .ok_or(StakingError::MathOverflow)?;
+
+    u64::try_from(weight).map_err(|_| error!(StakingError::MathOverflow))
+}
+
+/// A validator's direct SOL and alpha stake, converted by the subnet's weights
+fn self_stake_weight(stake_account: &StakeAccount, config: &SubnetStakeConfig) -> Result<u128> {
+    let sol_self = stake_account.self_stake()? as u128;
+
+    Ok(sol_self
+        .checked_mul(config.sol_weight as u128)
+        .and_then(|sol| {
+            (stake_account.alpha_amount as u128)
+                .checked_mul(config.alpha_weight as u128)
+                .and_then(|alpha| sol.checked_add(alpha))
+        })
+        .ok_or(StakingError::MathOverflow)?
+        / WEIGHT_DENOMINATOR as u128)
+}
+
+/// Load a subnet's stake config, falling back to defaults if it was never created
+pub fn load_stake_config(stake_config: &AccountInfo) -> Result<SubnetStakeConfig> {
+    if stake_config.owner != &crate::ID || stake_config.data_is_empty() {
+        return Ok(SubnetStakeConfig::default());
+    }
+
+    let data = stake_config.try_borrow_data()?;
+    SubnetStakeConfig::try_deserialize(&mut data.as_ref())
+}
+
+/// A balance is valid if it is a full exit or at least the subnet minimum
+fn meets_minimum(balance: u64, minimum: u64) -> bool {
+    balance == 0 || balance >= minimum
+}
+
+/// Refresh a permit holder's entry in the validator set after its stake
+/// dropped, revoking the permit if it fell below the subnet threshold.
+/// Returns whether the permit was revoked; the caller syncs the neuron.
+fn refresh_permit(
+    stake_account: &mut StakeAccount,
+    validator_set: Option<&mut Account<ValidatorSet>>,
+    weight: u64,
+    config: &SubnetStakeConfig,
+) -> Result<bool> {
+    if !stake_account.has_permit {
+        return Ok(false);
+    }
+
+    let validator_set = validator_set.ok_or(StakingError::MissingValidatorSet)?;
+    let validator = stake_account.validator;
+
+    if weight == 0 || weight < config.min_permit_stake {
+        validator_set.validators.retain(|e| e.validator != validator);
+        stake_account.has_permit = false;
+        stake_account.permit_changed_epoch = epoch_at(Clock::get()?.unix_timestamp);
+
+        msg!("Validator {} lost its permit in subnet {}", validator, stake_account.subnet_id);
+        return Ok(true);
+    }
+
+    if let Some(entry) = validator_set.validators.iter_mut().find(|e| e.validator == validator) {
+        entry.weight = weight;
+    }
+
+    Ok(false)
+}
+
+/// Epoch index for a unix timestamp
+pub fn epoch_at(unix_timestamp: i64) -> u64 {
+    (unix_timestamp / EPOCH_DURATION) as u64
+}
+
+/// Round a lock end up to the next `LOCK_EXPIRY_GRANULARITY` boundary
+fn lock_expiry(unix_timestamp: i64) -> i64 {
+    (unix_timestamp + LOCK_EXPIRY_GRANULARITY - 1) / LOCK_EXPIRY_GRANULARITY * LOCK_EXPIRY_GRANULARITY
+}
+
+/// Record `weight` for `validator` in the current epoch's snapshot, creating
+/// the snapshot on the subnet's first touch after the epoch boundary. Only the
+/// first touch per validator is kept, so callers pass the weight from before
+/// their own change and the entry reflects stake held when the epoch started.
+/// Once the snapshot is full further validators are skipped, and consensus
+/// rejects their weight commits for the epoch.
+fn record_stake_snapshot<'info>(
+    stake_snapshot: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    subnet_id: u16,
+    validator: Pubkey,
+    weight: u64,
+) -> Result<()> {
+    let epoch = epoch_at(Clock::get()?.unix_timestamp);
+    let subnet_bytes = subnet_id.to_le_bytes();
+    let epoch_bytes = epoch.to_le_bytes();
+    let (expected_snapshot, bump) = Pubkey::find_program_address(
+        &[b"stake_snapshot", &subnet_bytes, &epoch_bytes],
+        &crate::ID,
+    );
+    require_keys_eq!(stake_snapshot.key(), expected_snapshot, StakingError::InvalidSnapshot);
+
+    let mut snapshot = if stake_snapshot.data_is_empty() {
+        let space = 8 + StakeSnapshot::LEN;
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::CreateAccount {
+                    from: payer.clone(),
+                    to: stake_snapshot.clone(),
+                },
+                &[&[b"stake_snapshot", &subnet_bytes, &epoch_bytes, &[bump]]],
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &crate::ID,
+        )?;
+        StakeSnapshot {
+            subnet_id,
+            epoch,
+            payer: payer.key(),
+            entries: Vec::new(),
+        }
+    } else {
+        StakeSnapshot::try_deserialize(&mut stake_snapshot.try_borrow_data()?.as_ref())?
+    };
+
+    if snapshot.weight_of(&validator).is_some() {
+        return Ok(());
+    }
+    if !snapshot.record(validator, weight) {
+        msg!("Stake snapshot for epoch {} is full, {} not recorded", epoch, validator);
+        return Ok(());
+    }
+
+    let mut data = stake_snapshot.try_borrow_mut_data()?;
+    snapshot.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+/// Read the governor from a registry Subnet account
+pub fn subnet_governor(subnet: &AccountInfo) -> Result<Pubkey> {
+    let data = subnet.try_borrow_data()?;
+    require!(data.len() >= 34, StakingError::InvalidSubnet);
+
+    // Registry Subnet layout: id (2) | governor (32) | ...
+    Pubkey::try_from(&data[2..34]).map_err(|_| error!(StakingError::InvalidSubnet))
+}
+
+/// Read the validator permit limit from a registry Subnet account
+fn subnet_validator_limit(subnet: &AccountInfo) -> Result<u8> {
+    let data = subnet.try_borrow_data()?;
+    require!(data.len() >= 36, StakingError::InvalidSubnet);
+
+    // Registry Subnet layout: id (2) | governor (32) | max_neurons (1) | validator_limit (1) | ...
+    Ok(data[35])
+}
+
+/// Move lamports out of a program-owned PDA (stake account or treasury),
+/// keeping it rent exempt
+fn debit_stake_lamports<'info>(
+    stake_info: &AccountInfo<'info>,
+    recipient: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let remaining = stake_info
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(StakingError::InsufficientStake)?;
+    require!(
+        remaining >= Rent::get()?.minimum_balance(stake_info.data_len()),
+        StakingError::RentExemption
+    );
+
+    **stake_info.try_borrow_mut_lamports()? = remaining;
+    **recipient.try_borrow_mut_lamports()? += amount;
+
+    Ok(())
+}
+
+/// Mirror a validator's stake weight onto its registry Neuron
+fn sync_neuron_stake<'info>(
+    neuron: &AccountInfo<'info>,
+    registry_authority: &AccountInfo<'info>,
+    registry_program: &AccountInfo<'info>,
+    authority_bump: u8,
+    stake_weight: u64,
+) -> Result<()> {
+    let mut data = Vec::with_capacity(1 + 8);
+    data.push(REGISTRY_UPDATE_NEURON_STAKE);
+    data.extend_from_slice(&stake_weight.to_le_bytes());
+
+    invoke_registry(neuron, registry_authority, registry_program, authority_bump, data)
+}
+
+/// Mirror a validator's permit onto its registry Neuron's `is_validator` flag
+fn sync_neuron_permit<'info>(
+    neuron: &AccountInfo<'info>,
+    registry_authority: &AccountInfo<'info>,
+    registry_program: &AccountInfo<'info>,
+    authority_bump: u8,
+    is_validator: bool,
+) -> Result<()> {
+    let data = vec![REGISTRY_UPDATE_NEURON_PERMIT, is_validator as u8];
+
+    invoke_registry(neuron, registry_authority, registry_program, authority_bump, data)
+}
+
+/// Invoke a registry Neuron update signed by the `registry_authority` PDA
+fn invoke_registry<'info>(
+    neuron: &AccountInfo<'info>,
+    registry_authority: &AccountInfo<'info>,
+    registry_program: &AccountInfo<'info>,
+    authority_bump: u8,
+    data: Vec<u8>,
+) -> Result<()> {
+    let ix = Instruction {
+        program_id: registry_program.key(),
+        accounts: vec![
+            AccountMeta::new(neuron.key(), false),
+            AccountMeta::new_readonly(registry_authority.key(), true),
+        ],
+        data,
+    };
+
+    invoke_signed(
+        &ix,
+        &[neuron.clone(), registry_authority.clone(), registry_program.clone()],
+        &[&[b"registry_authority", &[authority_bump]]],
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16)]
+pub struct StakeValidator<'info> {
+    #[account(
+        init,
+        payer = validator,
+        space = 8 + StakeAccount::LEN,
+        seeds = [b"stake", validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub validator: Signer<'info>,
+
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &subnet_id.to_le_bytes(), validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16)]
+pub struct AddStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump,
+        has_one = validator @ StakingError::Unauthorized
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub validator: Signer<'info>,
+    
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &subnet_id.to_le_bytes(), validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16)]
+pub struct Delegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = delegator,
+        space = 8 + Delegation::LEN,
+        seeds = [b"delegation", delegator.key().as_ref(), validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub delegation: Account<'info, Delegation>,
+    
+    /// CHECK: Validator
+    pub validator: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub delegator: Signer<'info>,
+
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &subnet_id.to_le_bytes(), validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", stake_account.subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + UnstakeTicket::LEN,
+        seeds = [b"unstake_ticket", stake_account.key().as_ref(), &stake_account.unstake_nonce.to_le_bytes()],
+        bump
+    )]
+    pub ticket: Account<'info, UnstakeTicket>,
+    
+    /// CHECK: Validator or delegator
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// CHECK: Validator
+    pub validator: UncheckedAccount<'info>,
+    
+    /// CHECK: Optional delegation account
+    #[account(mut)]
+    pub delegation: Option<Account<'info, Delegation>>,
+    
+    /// Subnet validator set, required if the validator holds a permit
+    #[account(
+        mut,
+        seeds = [b"validator_set", stake_account.subnet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub validator_set: Option<Account<'info, ValidatorSet>>,
+
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &stake_account.subnet_id.to_le_bytes(), validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawUnstaked<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"unstake_ticket", stake_account.key().as_ref(), &ticket.ticket_id.to_le_bytes()],
+        bump,
+        has_one = owner @ StakingError::Unauthorized,
+        has_one = stake_account @ StakingError::Unauthorized,
+        close = owner
+    )]
+    pub ticket: Account<'info, UnstakeTicket>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16)]
+pub struct ConfigureSubnetStaking<'info> {
+    #[account(
+        init_if_needed,
+        payer = governor,
+        space = 8 + SubnetStakeConfig::LEN,
+        seeds = [b"stake_config", subnet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub stake_config: Account<'info, SubnetStakeConfig>,
+    
+    /// CHECK: Registry subnet account, read for its governor
+    #[account(
+        seeds = [b"subnet", &subnet_id.to_le_bytes()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID,
+        owner = REGISTRY_PROGRAM_ID
+    )]
+    pub subnet: UncheckedAccount<'info>,
+    
+    pub alpha_mint: Option<Account<'info, Mint>>,
+    
+    #[account(mut)]
+    pub governor: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16)]
+pub struct StakeAlpha<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump,
+        has_one = validator @ StakingError::Unauthorized
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    pub alpha_mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        token::mint = alpha_mint,
+        token::authority = validator
+    )]
+    pub validator_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = validator,
+        seeds = [b"alpha_vault", stake_account.key().as_ref()],
+        bump,
+        token::mint = alpha_mint,
+        token::authority = stake_account
+    )]
+    pub alpha_vault: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub validator: Signer<'info>,
+    
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &subnet_id.to_le_bytes(), validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnstakeAlpha<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        has_one = validator @ StakingError::Unauthorized
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", stake_account.subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(
+        init,
+        payer = validator,
+        space = 8 + UnstakeTicket::LEN,
+        seeds = [b"unstake_ticket", stake_account.key().as_ref(), &stake_account.unstake_nonce.to_le_bytes()],
+        bump
+    )]
+    pub ticket: Account<'info, UnstakeTicket>,
+    
+    #[account(mut)]
+    pub validator: Signer<'info>,
+    
+    /// Subnet validator set, required if the validator holds a permit
+    #[account(
+        mut,
+        seeds = [b"validator_set", stake_account.subnet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub validator_set: Option<Account<'info, ValidatorSet>>,
+    
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &stake_account.subnet_id.to_le_bytes(), validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawUnstakedAlpha<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"unstake_ticket", stake_account.key().as_ref(), &ticket.ticket_id.to_le_bytes()],
+        bump,
+        has_one = owner @ StakingError::Unauthorized,
+        has_one = stake_account @ StakingError::Unauthorized,
+        close = owner
+    )]
+    pub ticket: Account<'info, UnstakeTicket>,
+    
+    #[account(
+        mut,
+        seeds = [b"alpha_vault", stake_account.key().as_ref()],
+        bump
+    )]
+    pub alpha_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        token::mint = alpha_vault.mint
+    )]
+    pub owner_token: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16)]
+pub struct Redelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", source_validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub source_stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake", dest_validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub dest_stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    /// Subnet validator set, required if the source validator holds a permit
+    #[account(
+        mut,
+        seeds = [b"validator_set", subnet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub validator_set: Option<Account<'info, ValidatorSet>>,
+    
+    #[account(
+        mut,
+        seeds = [b"delegation", delegator.key().as_ref(), source_validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump,
+        has_one = delegator @ StakingError::Unauthorized
+    )]
+    pub source_delegation: Account<'info, Delegation>,
+    
+    #[account(
+        init_if_needed,
+        payer = delegator,
+        space = 8 + Delegation::LEN,
+        seeds = [b"delegation", delegator.key().as_ref(), dest_validator.key().as_ref(), &subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub dest_delegation: Account<'info, Delegation>,
+    
+    /// CHECK: Validator currently delegated to
+    pub source_validator: UncheckedAccount<'info>,
+    
+    /// CHECK: Validator to move the delegation to
+    #[account(constraint = dest_validator.key() != source_validator.key() @ StakingError::InvalidRedelegation)]
+    pub dest_validator: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub delegator: Signer<'info>,
+    
+    /// CHECK: Registry neuron for the source validator
+    #[account(
+        mut,
+        seeds = [b"neuron", &subnet_id.to_le_bytes(), source_validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub source_neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry neuron for the destination validator
+    #[account(
+        mut,
+        seeds = [b"neuron", &subnet_id.to_le_bytes(), dest_validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub dest_neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LockDelegation<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", stake_account.subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"delegation", delegator.key().as_ref(), stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        has_one = delegator @ StakingError::Unauthorized
+    )]
+    pub delegation: Account<'info, Delegation>,
+    
+    #[account(mut)]
+    pub delegator: Signer<'info>,
+    
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &stake_account.subnet_id.to_le_bytes(), stake_account.validator.as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireDelegationLock<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", stake_account.subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"delegation", delegation.delegator.as_ref(), stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub delegation: Account<'info, Delegation>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &stake_account.subnet_id.to_le_bytes(), stake_account.validator.as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SnapshotStake<'info> {
+    #[account(
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", stake_account.subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(subnet_id: u16, epoch: u64)]
+pub struct CloseStakeSnapshot<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake_snapshot", subnet_id.to_le_bytes().as_ref(), epoch.to_le_bytes().as_ref()],
+        bump,
+        has_one = payer,
+        close = payer
+    )]
+    pub stake_snapshot: Account<'info, StakeSnapshot>,
+    
+    /// CHECK: Snapshot creator receiving the rent, checked by `has_one`
+    #[account(mut)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCommission<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        has_one = validator @ StakingError::Unauthorized
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    pub validator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetAutoCompound<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        has_one = validator @ StakingError::Unauthorized
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    pub validator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegationAutoCompound<'info> {
+    #[account(
+        mut,
+        seeds = [b"delegation", delegator.key().as_ref(), delegation.validator.as_ref(), &delegation.subnet_id.to_le_bytes()],
+        bump,
+        has_one = delegator @ StakingError::Unauthorized
+    )]
+    pub delegation: Account<'info, Delegation>,
+    
+    pub delegator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreditRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Reward source (emissions treasury or keeper)
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimDelegatorRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"delegation", delegator.key().as_ref(), stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        has_one = delegator @ StakingError::Unauthorized
+    )]
+    pub delegation: Account<'info, Delegation>,
+    
+    #[account(mut)]
+    pub delegator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimValidatorRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        has_one = validator @ StakingError::Unauthorized
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(mut)]
+    pub validator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CompoundRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", stake_account.subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Current epoch's stake snapshot PDA, created on first touch
+    #[account(mut)]
+    pub stake_snapshot: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"delegation", delegation.delegator.as_ref(), stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub delegation: Option<Account<'info, Delegation>>,
+    
+    /// CHECK: Emissions program PDA signer
+    #[account(
+        seeds = [b"staking_authority"],
+        bump,
+        seeds::program = EMISSIONS_PROGRAM_ID
+    )]
+    pub emissions_authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &stake_account.subnet_id.to_le_bytes(), stake_account.validator.as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDelegation<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"delegation", delegator.key().as_ref(), stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        has_one = delegator @ StakingError::Unauthorized,
+        close = delegator
+    )]
+    pub delegation: Account<'info, Delegation>,
+    
+    #[account(mut)]
+    pub delegator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseStakeAccount<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        has_one = validator @ StakingError::Unauthorized,
+        close = validator
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// Alpha vault, required if the validator ever staked alpha
+    #[account(
+        mut,
+        seeds = [b"alpha_vault", stake_account.key().as_ref()],
+        bump
+    )]
+    pub alpha_vault: Option<Account<'info, TokenAccount>>,
+    
+    #[account(mut)]
+    pub validator: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Slash<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", stake_account.validator.as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", stake_account.subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Consensus program PDA signer
+    #[account(
+        seeds = [b"staking_authority"],
+        bump,
+        seeds::program = CONSENSUS_PROGRAM_ID
+    )]
+    pub consensus_authority: Signer<'info>,
+    
+    /// Protocol treasury receiving slashed stake
+    #[account(mut, seeds = [b"treasury"], bump)]
+    pub treasury: Account<'info, Treasury>,
+    
+    /// Alpha vault, required if the validator holds alpha
+    #[account(
+        mut,
+        seeds = [b"alpha_vault", stake_account.key().as_ref()],
+        bump
+    )]
+    pub alpha_vault: Option<Account<'info, TokenAccount>>,
+    
+    /// Treasury token account for the subnet's alpha, required with `alpha_vault`
+    #[account(mut, token::authority = treasury)]
+    pub treasury_alpha: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Option<Program<'info, Token>>,
+    
+    /// Subnet validator set, required if the validator holds a permit
+    #[account(
+        mut,
+        seeds = [b"validator_set", stake_account.subnet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub validator_set: Option<Account<'info, ValidatorSet>>,
+    
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &stake_account.subnet_id.to_le_bytes(), stake_account.validator.as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTreasury<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Treasury::LEN,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::PoiStaking>,
+    
+    #[account(constraint = program_data.upgrade_authority_address == Some(payer.key()) @ StakingError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+    
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump,
+        has_one = authority @ StakingError::Unauthorized
+    )]
+    pub treasury: Account<'info, Treasury>,
+    
+    pub authority: Signer<'info>,
+    
+    /// CHECK: Any account receiving the lamports
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasuryAlpha<'info> {
+    #[account(
+        seeds = [b"treasury"],
+        bump,
+        has_one = authority @ StakingError::Unauthorized
+    )]
+    pub treasury: Account<'info, Treasury>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(mut, token::authority = treasury)]
+    pub treasury_alpha: Account<'info, TokenAccount>,
+    
+    #[account(mut, token::mint = treasury_alpha.mint)]
+    pub recipient_token: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey, subnet_id: u16)]
+pub struct GetVotingPower<'info> {
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    /// The owner's own validator stake account, if they run a validator
+    #[account(
+        seeds = [b"stake", owner.as_ref(), &subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Option<Account<'info, StakeAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePermitEligibility<'info> {
+    #[account(
+        mut,
+        seeds = [b"stake", validator.key().as_ref(), &stake_account.subnet_id.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    /// CHECK: Subnet stake config PDA, defaults apply if it was never created
+    #[account(seeds = [b"stake_config", stake_account.subnet_id.to_le_bytes().as_ref()], bump)]
+    pub stake_config: UncheckedAccount<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + ValidatorSet::LEN,
+        seeds = [b"validator_set", stake_account.subnet_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub validator_set: Account<'info, ValidatorSet>,
+    
+    /// CHECK: Registry subnet account, read for its validator limit
+    #[account(
+        seeds = [b"subnet", &stake_account.subnet_id.to_le_bytes()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID,
+        owner = REGISTRY_PROGRAM_ID
+    )]
+    pub subnet: UncheckedAccount<'info>,
+    
+    /// CHECK: Authority (any keeper, pays for the validator set on first use)
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// CHECK: Validator
+    pub validator: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry neuron for the validator, owned by the registry program
+    #[account(
+        mut,
+        seeds = [b"neuron", &stake_account.subnet_id.to_le_bytes(), validator.key().as_ref()],
+        bump,
+        seeds::program = REGISTRY_PROGRAM_ID
+    )]
+    pub neuron: UncheckedAccount<'info>,
+    
+    /// Stake account of the permit holder being displaced, if any
+    #[account(mut)]
+    pub evicted_stake_account: Option<Account<'info, StakeAccount>>,
+    
+    /// CHECK: Registry neuron of the displaced holder, verified in the handler
+    #[account(mut)]
+    pub evicted_neuron: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: PDA signer the registry accepts stake updates from
+    #[account(seeds = [b"registry_authority"], bump)]
+    pub registry_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Registry program
+    #[account(address = REGISTRY_PROGRAM_ID)]
+    pub registry_program: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct StakeAccount {
+    pub validator: Pubkey,
+    pub subnet_id: u16,
+    /// Total SOL staked on the validator: its own stake plus `delegated_amount`
+    pub amount: u64,
+    /// SOL delegated to the validator, included in `amount`
+    pub delegated_amount: u64,
+    pub unstake_pending: u64,
+    pub unstake_nonce: u64,
+    pub commission_bps: u16,
+    pub pending_commission_bps: u16,
+    pub commission_effective_at: i64,
+    pub reward_per_share: u128,
+    pub validator_rewards: u64,
+    pub delegation_scale: u128,
+    pub alpha_mint: Pubkey,
+    pub alpha_amount: u64,
+    pub has_permit: bool,
+    pub permit_changed_epoch: u64,
+    pub delegation_count: u32,
+    pub auto_compound: bool,
+    /// Alpha waiting in unstake tickets, still held by the alpha vault
+    pub alpha_unstake_pending: u64,
+    /// Scale of pending unstake tickets, reduced by each slash
+    pub unstake_scale: u128,
+    /// Lock bonus of its delegations by expiry, sorted by `expires_at`
+    pub lock_schedule: Vec<LockExpiry>,
+    /// Outstanding SOL unstake tickets
+    pub unstake_tickets: u32,
+    /// Outstanding alpha unstake tickets
+    pub alpha_unstake_tickets: u32,
+}
+
+impl StakeAccount {
+    pub const LEN: usize = 32 + 2 + 8 + 8 + 8 + 8 + 2 + 2 + 8 + 16 + 8 + 16 + 32 + 8 + 1 + 8 + 4 + 1 + 8 + 16
+        + (4 + MAX_LOCK_EXPIRIES * LockExpiry::LEN) + 4 + 4;
+
+    /// The validator's own SOL stake, `amount` less delegations
+    pub fn self_stake(&self) -> Result<u64> {
+        self.amount
+            .checked_sub(self.delegated_amount)
+            .ok_or(error!(StakingError::MathOverflow))
+    }
+
+    /// Lock bonus of delegations whose locks have not expired by `now`
+    pub fn active_lock_bonus(&self, now: i64) -> u64 {
+        self.lock_schedule
+            .iter()
+            .filter(|e| e.expires_at > now)
+            .fold(0u64, |acc, e| acc.saturating_add(e.bonus))
+    }
+
+    /// Swap one delegation's lock bonus in the schedule after it changes,
+    /// dropping expired entries
+    pub fn update_delegation_bonus(&mut self, old_lock: LockExpiry, new_lock: LockExpiry, now: i64) -> Result<()> {
+        if let Some(entry) = self.lock_schedule.iter_mut().find(|e| e.expires_at == old_lock.expires_at) {
+            // Lazily slashed delegations can round above the schedule by dust
+            entry.bonus = entry.bonus.saturating_sub(old_lock.bonus);
+        }
+        self.lock_schedule.retain(|e| e.expires_at > now && e.bonus > 0);
+
+        if new_lock.bonus > 0 && new_lock.expires_at > now {
+            match self
+                .lock_schedule
+                .binary_search_by_key(&new_lock.expires_at, |e| e.expires_at)
+            {
+                Ok(i) => {
+                    let entry = &mut self.lock_schedule[i];
+                    entry.bonus = entry
+                        .bonus
+                        .checked_add(new_lock.bonus)
+                        .ok_or(StakingError::MathOverflow)?;
+                }
+                Err(i) => {
+                    require!(
+                        self.lock_schedule.len() < MAX_LOCK_EXPIRIES,
+                        StakingError::InvalidLockDuration
+                    );
+                    self.lock_schedule.insert(i, new_lock);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take `slash_bps` of the self, delegated, pending and alpha balances.
+    /// Delegations and unstake tickets pick up the reduced scales the next
+    /// time they are touched.
+    pub fn slash_stake(&mut self, slash_bps: u16) -> Result<SlashedStake> {
+        let slashed = |amount: u64| (amount as u128 * slash_bps as u128 / 10000) as u64;
+
+        let slashed_stake = SlashedStake {
+            self_slashed: slashed(self.self_stake()?),
+            delegated_slashed: slashed(self.delegated_amount),
+            pending_slashed: slashed(self.unstake_pending),
+            alpha_slashed: slashed(self.alpha_amount),
+            alpha_pending_slashed: slashed(self.alpha_unstake_pending),
+        };
+
+        let delegation_scale = self.delegation_scale * (10000 - slash_bps) as u128 / 10000;
+        let unstake_scale = if self.unstake_scale == 0 {
+            REWARD_PRECISION
+        } else {
+            self.unstake_scale
+        } * (10000 - slash_bps) as u128 / 10000;
+        require!(delegation_scale > 0 && unstake_scale > 0, StakingError::MathOverflow);
+
+        self.amount -= slashed_stake.self_slashed + slashed_stake.delegated_slashed;
+        self.delegated_amount -= slashed_stake.delegated_slashed;
+        for entry in self.lock_schedule.iter_mut() {
+            entry.bonus -= slashed(entry.bonus);
+        }
+        self.delegation_scale = delegation_scale;
+        self.unstake_pending -= slashed_stake.pending_slashed;
+        self.alpha_amount -= slashed_stake.alpha_slashed;
+        self.alpha_unstake_pending -= slashed_stake.alpha_pending_slashed;
+        self.unstake_scale = unstake_scale;
+
+        Ok(slashed_stake)
+    }
+
+    /// Drop a closed delegation. Delegations are scaled by slashes one by one
+    /// and round down, so once the last one is gone, what rounding left in
+    /// `delegated_amount` and the lock schedule is cleared. Its lamports stay
+    /// in the account and are refunded with the rent when it closes.
+    pub fn remove_delegation(&mut self) -> Result<()> {
+        self.delegation_count = self.delegation_count.saturating_sub(1);
+        if self.delegation_count == 0 {
+            self.amount = self.self_stake()?;
+            self.delegated_amount = 0;
+            self.lock_schedule.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Whether nothing is left staked, pending or owed, so the account can close
+    pub fn is_empty(&self) -> bool {
+        self.amount == 0
+            && self.unstake_pending == 0
+            && self.alpha_amount == 0
+            && self.validator_rewards == 0
+            && self.delegation_count == 0
+            && !self.has_permit
+    }
+
+    /// Move `amount` of `asset` into the pending total behind `ticket`,
+    /// which takes the next ticket id and the current unstake scale
+    pub fn open_ticket(&mut self, ticket: &mut UnstakeTicket, asset: StakeAsset, amount: u64) -> Result<()> {
+        let (pending, tickets) = match asset {
+            StakeAsset::Sol => (&mut self.unstake_pending, &mut self.unstake_tickets),
+            StakeAsset::Alpha => (&mut self.alpha_unstake_pending, &mut self.alpha_unstake_tickets),
+        };
+        *pending = pending.checked_add(amount).ok_or(StakingError::MathOverflow)?;
+        *tickets += 1;
+
+        ticket.ticket_id = self.unstake_nonce;
+        ticket.asset = asset;
+        ticket.amount = amount;
+        ticket.scale_checkpoint = self.unstake_scale;
+        self.unstake_nonce += 1;
+
+        Ok(())
+    }
+
+    /// Settle a redeemed ticket against the pending total of its asset and
+    /// return the amount it pays out. Tickets are scaled by slashes one by
+    /// one and round down, so the last ticket out also takes what rounding
+    /// left in the pending total.
+    pub fn redeem_ticket(&mut self, ticket: &UnstakeTicket) -> Result<u64> {
+        let amount = ticket.redeemable(self.unstake_scale)?;
+        let (pending, tickets) = match ticket.asset {
+            StakeAsset::Sol => (&mut self.unstake_pending, &mut self.unstake_tickets),
+            StakeAsset::Alpha => (&mut self.alpha_unstake_pending, &mut self.alpha_unstake_tickets),
+        };
+        require!(*pending >= amount, StakingError::InsufficientUnstakePending);
+
+        let amount = if *tickets == 1 { *pending } else { amount };
+        *tickets = tickets.saturating_sub(1);
+        *pending -= amount;
+
+        Ok(amount)
+    }
+
+    /// Promote a scheduled commission change once its delay has passed
+    pub fn apply_pending_commission(&mut self, now: i64) {
+        if self.commission_effective_at != 0 && now >= self.commission_effective_at {
+            self.commission_bps = self.pending_commission_bps;
+            self.commission_effective_at = 0;
+        }
+    }
+}
+
+/// Balances taken by one slash
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SlashedStake {
+    pub self_slashed: u64,
+    pub delegated_slashed: u64,
+    pub pending_slashed: u64,
+    pub alpha_slashed: u64,
+    pub alpha_pending_slashed: u64,
+}
+
+#[account]
+pub struct UnstakeTicket {
+    pub owner: Pubkey,
+    pub stake_account: Pubkey,
+    pub ticket_id: u64,
+    pub asset: StakeAsset,
+    pub amount: u64,
+    pub unlock_at: i64,
+    pub scale_checkpoint: u128,
+}
+
+impl UnstakeTicket {
+    pub const LEN: usize = 32 + 32 + 8 + 1 + 8 + 8 + 16;
+
+    /// Amount the ticket redeems for after slashes since it was created
+    pub fn redeemable(&self, unstake_scale: u128) -> Result<u64> {
+        if self.scale_checkpoint == 0 || self.scale_checkpoint == unstake_scale {
+            return Ok(self.amount);
+        }
+
+        u64::try_from(self.amount as u128 * unstake_scale / self.scale_checkpoint)
+            .map_err(|_| error!(StakingError::MathOverflow))
+    }
+}
+
+/// Protocol treasury holding slashed stake (`[b"treasury"]`)
+#[account]
+pub struct Treasury {
+    pub authority: Pubkey,
+}
+
+impl Treasury {
+    pub const LEN: usize = 32;
+}
+
+/// Lock bonus that stops counting at `expires_at`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct LockExpiry {
+    pub expires_at: i64,
+    pub bonus: u64,
+}
+
+impl LockExpiry {
+    pub const LEN: usize = 8 + 8;
+}
+
+/// Validator stake weights as of the start of an epoch (`[b"stake_snapshot", subnet, epoch]`)
+#[account]
+pub struct StakeSnapshot {
+    pub subnet_id: u16,
+    pub epoch: u64,
+    /// Refunded the rent when the snapshot is closed
+    pub payer: Pubkey,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+impl StakeSnapshot {
+    pub const LEN: usize = 2 + 8 + 32 + 4 + MAX_SNAPSHOT_VALIDATORS * SnapshotEntry::LEN;
+
+    pub fn weight_of(&self, validator: &Pubkey) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|e| e.validator == *validator)
+            .map(|e| e.weight)
+    }
+
+    /// Keep the first weight recorded for `validator`. Returns whether the
+    /// validator is now held, which fails only once the snapshot is full.
+    pub fn record(&mut self, validator: Pubkey, weight: u64) -> bool {
+        if self.weight_of(&validator).is_some() {
+            return true;
+        }
+        if self.entries.len() >= MAX_SNAPSHOT_VALIDATORS {
+            return false;
+        }
+        self.entries.push(SnapshotEntry { validator, weight });
+        true
+    }
+}
+
+/// Current validator permit holders and their weights (`[b"validator_set", subnet]`)
+#[account]
+pub struct ValidatorSet {
+    pub subnet_id: u16,
+    pub validators: Vec<SnapshotEntry>,
+}
+
+impl ValidatorSet {
+    pub const LEN: usize = 2 + 4 + MAX_VALIDATOR_PERMITS * SnapshotEntry::LEN;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SnapshotEntry {
+    pub validator: Pubkey,
+    pub weight: u64,
+}
+
+impl SnapshotEntry {
+    pub const LEN: usize = 32 + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StakeAsset {
+    Sol,
+    Alpha,
+}
+
+/// Per-subnet staking parameters, set by the subnet governor
+#[account]
+pub struct SubnetStakeConfig {
+    pub subnet_id: u16,
+    pub alpha_mint: Pubkey,
+    pub sol_weight: u64,
+    pub alpha_weight: u64,
+    pub delegation_weight: u64,
+    pub max_delegation_ratio: u64,
+    pub min_permit_stake: u64,
+    pub min_self_stake: u64,
+    pub min_delegation: u64,
+}
+
+impl SubnetStakeConfig {
+    pub const LEN: usize = 2 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8;
+}
+
+impl Default for SubnetStakeConfig {
+    fn default() -> Self {
+        Self {
+            subnet_id: 0,
+            alpha_mint: Pubkey::default(),
+            sol_weight: WEIGHT_DENOMINATOR,
+            alpha_weight: 0,
+            delegation_weight: DEFAULT_DELEGATION_WEIGHT,
+            max_delegation_ratio: 0,
+            min_permit_stake: 0,
+            min_self_stake: 0,
+            min_delegation: 0,
+        }
+    }
+}
+
+#[account]
+pub struct Delegation {
+    pub delegator: Pubkey,
+    pub validator: Pubkey,
+    pub subnet_id: u16,
+    pub amount: u64,
+    pub reward_debt: u128,
+    pub pending_rewards: u64,
+    pub scale_checkpoint: u128,
+    pub last_redelegated_at: i64,
+    pub lock_multiplier_bps: u16,
+    pub locked_until: i64,
+    pub auto_compound: bool,
+    /// Part of `amount` the lock multiplier applies to, fixed when locked
+    pub locked_amount: u64,
+}
+
+impl Delegation {
+    pub const LEN: usize = 32 + 32 + 2 + 8 + 16 + 8 + 16 + 8 + 2 + 8 + 1 + 8;
+
+    pub fn is_locked(&self, now: i64) -> bool {
+        now < self.locked_until
+    }
+
+    /// Extra stake weight the lock multiplier adds on top of `amount`
+    pub fn lock_bonus(&self) -> u64 {
+        let multiplier = self.lock_multiplier_bps as u64;
+        if multiplier <= WEIGHT_DENOMINATOR {
+            return 0;
+        }
+
+        (self.locked_amount.min(self.amount) as u128 * (multiplier - WEIGHT_DENOMINATOR) as u128
+            / WEIGHT_DENOMINATOR as u128) as u64
+    }
+
+    /// The lock bonus and when it expires, as held in the validator's schedule
+    pub fn lock_entry(&self) -> LockExpiry {
+        LockExpiry {
+            expires_at: self.locked_until,
+            bonus: self.lock_bonus(),
+        }
+    }
+
+    /// Drop the multiplier once the lock has run out
+    pub fn release_expired_lock(&mut self, now: i64) {
+        if !self.is_locked(now) {
+            self.lock_multiplier_bps = 0;
+            self.locked_amount = 0;
+        }
+    }
+
+    /// Stake in units that slashing does not change, used for reward accounting
+    pub fn shares(&self) -> u128 {
+        if self.scale_checkpoint == 0 {
+            self.amount as u128
+        } else {
+            self.amount as u128 * REWARD_PRECISION / self.scale_checkpoint
+        }
+    }
+
+    /// Apply any slashes to `amount` since the last checkpoint
+    pub fn apply_slash(&mut self, delegation_scale: u128) -> Result<()> {
+        if self.scale_checkpoint != 0 && self.scale_checkpoint != delegation_scale {
+            self.amount = u64::try_from(
+                self.amount as u128 * delegation_scale / self.scale_checkpoint,
+            )
+            .map_err(|_| StakingError::MathOverflow)?;
+            self.locked_amount = (self.locked_amount as u128 * delegation_scale
+                / self.scale_checkpoint) as u64;
+        }
+        self.scale_checkpoint = delegation_scale;
+
+        Ok(())
+    }
+
+    /// Accrue rewards earned at `reward_per_share` since the last settlement
+    pub fn settle_rewards(&mut self, reward_per_share: u128) -> Result<()> {
+        let accumulated = self
+            .shares()
+            .checked_mul(reward_per_share)
+            .ok_or(StakingError::MathOverflow)?
+            / REWARD_PRECISION;
+        let earned = u64::try_from(accumulated.saturating_sub(self.reward_debt))
+            .map_err(|_| StakingError::MathOverflow)?;
+
+        self.pending_rewards = self
+            .pending_rewards
+            .checked_add(earned)
+            .ok_or(StakingError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// Checkpoint the accumulator after `amount` changes
+    pub fn reset_reward_debt(&mut self, reward_per_share: u128) -> Result<()> {
+        self.reward_debt = self
+            .shares()
+            .checked_mul(reward_per_share)
+            .ok_or(StakingError::MathOverflow)?
+            / REWARD_PRECISION;
+
+        Ok(())
+    }
+}
+
+/// Return data of `voting_power`, in SOL-equivalent weight units
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct VotingPower {
+    pub owner: Pubkey,
+    pub subnet_id: u16,
+    pub self_stake: u64,
+    pub delegated_stake: u64,
+    pub total: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlashReason {
+    ConsensusDeviation,
+    InvalidWeights,
+}
+
+#[event]
+pub struct StakeSlashed {
+    pub validator: Pubkey,
+    pub subnet_id: u16,
+    pub slash_bps: u16,
+    pub reason: SlashReason,
+    pub self_slashed: u64,
+    pub delegated_slashed: u64,
+    pub pending_slashed: u64,
+    pub alpha_slashed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RewardsCompounded {
+    pub validator: Pubkey,
+    pub subnet_id: u16,
+    pub delegator: Option<Pubkey>,
+    pub validator_amount: u64,
+    pub delegator_amount: u64,
+    pub timestamp: i64,
+}
+
+#[error_code]
+pub enum StakingError {
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Insufficient stake")]
+    InsufficientStake,
     #[msg("Cannot unstake delegated funds")]
     CannotUnstakeDelegated,
     #[msg("Unauthorized")]
@@ -301,5 +3328,300 @@ pub enum StakingError {
     CooldownActive,
     #[msg("Insufficient unstake pending")]
     InsufficientUnstakePending,
+    #[msg("Withdrawal would leave the stake account below rent exemption")]
+    RentExemption,
+    #[msg("Commission exceeds the maximum")]
+    InvalidCommission,
+    #[msg("No rewards to claim")]
+    NoRewardsToClaim,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Invalid slash fraction")]
+    InvalidSlashFraction,
+    #[msg("Cannot redelegate to the same validator")]
+    InvalidRedelegation,
+    #[msg("Delegation was redelegated too recently")]
+    RedelegationTooSoon,
+    #[msg("Wrong stake asset")]
+    WrongStakeAsset,
+    #[msg("Invalid subnet")]
+    InvalidSubnet,
+    #[msg("Invalid stake weight configuration")]
+    InvalidStakeWeightConfig,
+    #[msg("Stake snapshot does not match the current epoch")]
+    InvalidSnapshot,
+    #[msg("Stake snapshot is still within its retention window")]
+    SnapshotRetained,
+    #[msg("Displaced permit holder's accounts are missing or wrong")]
+    MissingEvictedValidator,
+    #[msg("Validator set is required for a permit holder")]
+    MissingValidatorSet,
+    #[msg("Balance would be below the subnet minimum without fully exiting")]
+    BelowMinimumStake,
+    #[msg("Account still holds stake, pending withdrawals or rewards")]
+    AccountNotEmpty,
+    #[msg("Delegation is time-locked")]
+    DelegationLocked,
+    #[msg("Lock duration is not a supported tier or would shorten the lock")]
+    InvalidLockDuration,
+    #[msg("Voting power accounts are not the owner's delegations in this subnet")]
+    InvalidVotingAccounts,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stake_account(self_stake: u64, delegations: &[u64]) -> StakeAccount {
+        let delegated: u64 = delegations.iter().sum();
+        StakeAccount {
+            validator: Pubkey::new_unique(),
+            subnet_id: 1,
+            amount: self_stake + delegated,
+            delegated_amount: delegated,
+            unstake_pending: 0,
+            unstake_nonce: 0,
+            commission_bps: 0,
+            pending_commission_bps: 0,
+            commission_effective_at: 0,
+            reward_per_share: 0,
+            validator_rewards: 0,
+            delegation_scale: REWARD_PRECISION,
+            alpha_mint: Pubkey::default(),
+            alpha_amount: 0,
+            has_permit: false,
+            permit_changed_epoch: 0,
+            delegation_count: delegations.len() as u32,
+            auto_compound: false,
+            alpha_unstake_pending: 0,
+            unstake_scale: REWARD_PRECISION,
+            lock_schedule: Vec::new(),
+            unstake_tickets: 0,
+            alpha_unstake_tickets: 0,
+        }
+    }
+
+    fn delegation(amount: u64) -> Delegation {
+        Delegation {
+            delegator: Pubkey::new_unique(),
+            validator: Pubkey::default(),
+            subnet_id: 1,
+            amount,
+            reward_debt: 0,
+            pending_rewards: 0,
+            scale_checkpoint: REWARD_PRECISION,
+            last_redelegated_at: 0,
+            lock_multiplier_bps: 0,
+            locked_until: 0,
+            auto_compound: false,
+            locked_amount: 0,
+        }
+    }
+
+    fn ticket() -> UnstakeTicket {
+        UnstakeTicket {
+            owner: Pubkey::new_unique(),
+            stake_account: Pubkey::default(),
+            ticket_id: 0,
+            asset: StakeAsset::Sol,
+            amount: 0,
+            unlock_at: 0,
+            scale_checkpoint: 0,
+        }
+    }
+
+    /// The delegator side of `unstake`, moving a whole delegation into a ticket
+    fn unstake_delegation(stake_account: &mut StakeAccount, delegation: &mut Delegation) -> UnstakeTicket {
+        delegation.apply_slash(stake_account.delegation_scale).unwrap();
+        let amount = delegation.amount;
+        delegation.amount = 0;
+        stake_account.amount -= amount;
+        stake_account.delegated_amount = stake_account.delegated_amount.saturating_sub(amount);
+
+        let mut ticket = ticket();
+        stake_account.open_ticket(&mut ticket, StakeAsset::Sol, amount).unwrap();
+        ticket
+    }
+
+    #[test]
+    fn slashed_stake_can_be_fully_withdrawn_and_closed() {
+        let mut stake_account = stake_account(1_001, &[333, 333, 337]);
+        let mut delegations = [delegation(333), delegation(333), delegation(337)];
+
+        stake_account.slash_stake(333).unwrap();
+
+        // One delegator leaves between the slashes, the rest after
+        let mut tickets = vec![unstake_delegation(&mut stake_account, &mut delegations[0])];
+        stake_account.slash_stake(777).unwrap();
+        for delegation in delegations[1..].iter_mut() {
+            tickets.push(unstake_delegation(&mut stake_account, delegation));
+        }
+
+        let self_stake = stake_account.self_stake().unwrap();
+        stake_account.amount -= self_stake;
+        let mut self_ticket = ticket();
+        stake_account.open_ticket(&mut self_ticket, StakeAsset::Sol, self_stake).unwrap();
+        tickets.push(self_ticket);
+        stake_account.slash_stake(101).unwrap();
+
+        let pending = stake_account.unstake_pending;
+        let scaled: u64 = tickets
+            .iter()
+            .map(|t| t.redeemable(stake_account.unstake_scale).unwrap())
+            .sum();
+        assert!(scaled < pending);
+
+        let redeemed: u64 = tickets
+            .iter()
+            .map(|t| stake_account.redeem_ticket(t).unwrap())
+            .sum();
+        assert_eq!(redeemed, pending);
+
+        // Rounding leaves dust in the aggregate until the last delegation closes
+        assert!(stake_account.delegated_amount > 0);
+        for _ in &delegations {
+            stake_account.remove_delegation().unwrap();
+        }
+        assert_eq!(stake_account.amount, 0);
+        assert_eq!(stake_account.delegated_amount, 0);
+        assert_eq!(stake_account.unstake_tickets, 0);
+        assert!(stake_account.is_empty());
+    }
+
+    #[test]
+    fn apply_slash_scales_amount_and_locked_amount() {
+        let mut delegation = delegation(1_000);
+        delegation.locked_amount = 500;
+
+        let scale = REWARD_PRECISION * 9 / 10;
+        delegation.apply_slash(scale).unwrap();
+        assert_eq!(delegation.amount, 900);
+        assert_eq!(delegation.locked_amount, 450);
+        assert_eq!(delegation.scale_checkpoint, scale);
+
+        // Already at the current scale
+        delegation.apply_slash(scale).unwrap();
+        assert_eq!(delegation.amount, 900);
+        assert_eq!(delegation.locked_amount, 450);
+    }
+
+    #[test]
+    fn apply_slash_only_checkpoints_legacy_delegations() {
+        let mut delegation = delegation(1_000);
+        delegation.scale_checkpoint = 0;
+
+        delegation.apply_slash(REWARD_PRECISION / 2).unwrap();
+        assert_eq!(delegation.amount, 1_000);
+        assert_eq!(delegation.scale_checkpoint, REWARD_PRECISION / 2);
+    }
+
+    #[test]
+    fn redeemable_scales_by_slashes_since_ticket_opened() {
+        let mut ticket = ticket();
+        ticket.amount = 1_000;
+
+        // Legacy tickets carry no checkpoint
+        assert_eq!(ticket.redeemable(REWARD_PRECISION / 2).unwrap(), 1_000);
+
+        ticket.scale_checkpoint = REWARD_PRECISION;
+        assert_eq!(ticket.redeemable(REWARD_PRECISION).unwrap(), 1_000);
+        assert_eq!(ticket.redeemable(REWARD_PRECISION * 3 / 4).unwrap(), 750);
+        assert_eq!(ticket.redeemable(0).unwrap(), 0);
+
+        // Rounds down, leaving the dust for the last ticket out
+        ticket.scale_checkpoint = REWARD_PRECISION / 3;
+        assert_eq!(ticket.redeemable(REWARD_PRECISION / 6).unwrap(), 499);
+    }
+
+    #[test]
+    fn stake_weight_counts_delegations_at_delegation_weight() {
+        let config = SubnetStakeConfig::default();
+
+        assert_eq!(stake_weight_at(&stake_account(1_000, &[]), &config, 0).unwrap(), 1_000);
+        // 1000 + 0.18 * 1000
+        assert_eq!(stake_weight_at(&stake_account(1_000, &[600, 400]), &config, 0).unwrap(), 1_180);
+
+        let config = SubnetStakeConfig {
+            alpha_weight: WEIGHT_DENOMINATOR / 2,
+            ..SubnetStakeConfig::default()
+        };
+        let mut account = stake_account(1_000, &[]);
+        account.alpha_amount = 400;
+        assert_eq!(stake_weight_at(&account, &config, 0).unwrap(), 1_200);
+    }
+
+    #[test]
+    fn stake_weight_caps_delegations_by_self_stake() {
+        let config = SubnetStakeConfig {
+            max_delegation_ratio: WEIGHT_DENOMINATOR / 2,
+            ..SubnetStakeConfig::default()
+        };
+
+        // Delegations count for at most half the self stake: 1000 + 0.18 * 500
+        assert_eq!(stake_weight_at(&stake_account(1_000, &[1_000]), &config, 0).unwrap(), 1_090);
+        assert_eq!(stake_weight_at(&stake_account(1_000, &[200]), &config, 0).unwrap(), 1_036);
+        assert_eq!(stake_weight_at(&stake_account(0, &[1_000]), &config, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn stake_weight_counts_lock_bonus_until_it_expires() {
+        let config = SubnetStakeConfig::default();
+        let mut account = stake_account(1_000, &[1_000]);
+        account.lock_schedule.push(LockExpiry { expires_at: 100, bonus: 500 });
+
+        // 1000 + 0.18 * (1000 + 500)
+        assert_eq!(stake_weight_at(&account, &config, 99).unwrap(), 1_270);
+        assert_eq!(stake_weight_at(&account, &config, 100).unwrap(), 1_180);
+    }
+
+    #[test]
+    fn stake_weight_does_not_overflow_at_u64_max() {
+        let config = SubnetStakeConfig {
+            sol_weight: MAX_CONVERSION_WEIGHT,
+            ..SubnetStakeConfig::default()
+        };
+
+        assert!(stake_weight_at(&stake_account(u64::MAX, &[]), &config, 0).is_err());
+        assert!(stake_weight_at(&stake_account(u64::MAX / 100, &[]), &config, 0).is_ok());
+    }
+
+    fn snapshot() -> StakeSnapshot {
+        StakeSnapshot {
+            subnet_id: 1,
+            epoch: 0,
+            payer: Pubkey::default(),
+            entries: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn snapshot_keeps_first_recorded_weight() {
+        let mut snapshot = snapshot();
+        let validator = Pubkey::new_unique();
+
+        assert!(snapshot.record(validator, 1_000));
+        assert!(snapshot.record(validator, 10));
+        assert_eq!(snapshot.weight_of(&validator), Some(1_000));
+        assert_eq!(snapshot.entries.len(), 1);
+        assert_eq!(snapshot.weight_of(&Pubkey::new_unique()), None);
+    }
+
+    #[test]
+    fn full_snapshot_rejects_new_validators_only() {
+        let mut snapshot = snapshot();
+        let first = Pubkey::new_unique();
+
+        assert!(snapshot.record(first, 1));
+        for weight in 1..MAX_SNAPSHOT_VALIDATORS as u64 {
+            assert!(snapshot.record(Pubkey::new_unique(), weight));
+        }
+
+        let late = Pubkey::new_unique();
+        assert!(!snapshot.record(late, 1));
+        assert_eq!(snapshot.weight_of(&late), None);
+        assert!(snapshot.record(first, 2));
+        assert_eq!(snapshot.weight_of(&first), Some(1));
+        assert_eq!(snapshot.entries.len(), MAX_SNAPSHOT_VALIDATORS);
+    }
+}
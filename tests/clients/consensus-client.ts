@@ -0,0 +1,37 @@
+import { PublicKey } from '@solana/web3.js';
+import { createHash } from 'crypto';
+import { serializeU16, serializeU64 } from '../utils/test-utils.js';
+
+export const CONSENSUS_PROGRAM_ID = new PublicKey('poiConsensus1111111111111111111111111111111');
+
+export interface WeightEntry {
+  minerUid: number;
+  weight: bigint;
+}
+
+/**
+ * Commitment passed to `commit_weights`: sha256 over the validator hotkey,
+ * subnet_id (u16 LE), epoch (u64 LE), each entry's miner_uid (u16 LE) and
+ * weight (u64 LE), then the 32-byte salt. Must match `weights_commitment`.
+ */
+export function weightsCommitment(
+  validator: PublicKey,
+  subnetId: number,
+  epoch: number | bigint,
+  weights: WeightEntry[],
+  salt: Buffer
+): Buffer {
+  if (salt.length !== 32) {
+    throw new Error(`salt must be 32 bytes, got ${salt.length}`);
+  }
+  const hash = createHash('sha256');
+  hash.update(validator.toBuffer());
+  hash.update(serializeU16(subnetId));
+  hash.update(serializeU64(epoch));
+  for (const entry of weights) {
+    hash.update(serializeU16(entry.minerUid));
+    hash.update(serializeU64(entry.weight));
+  }
+  hash.update(salt);
+  return hash.digest();
+}
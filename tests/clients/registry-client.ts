@@ -22,7 +22,10 @@ export enum RegistryInstruction {
   RegisterNeuron = 1,
   UpdateSubnetConfig = 2,
   PruneNeuron = 3,
-  UpdateNeuronStatus = 4,
+  UpdateNeuronStatus = 4, // CPI-only, signed by the consensus program
+  DissolveSubnet = 5,
+  UpdateNeuronStake = 6, // CPI-only, signed by the staking program
+  UpdateNeuronPermit = 7, // CPI-only, signed by the staking program
 }
 
 export interface CreateSubnetParams {
@@ -49,12 +52,8 @@ export interface PruneNeuronParams {
   uid: number;
 }
 
-export interface UpdateNeuronStatusParams {
-  rank?: number | bigint;
-  trust?: number | bigint;
-  incentive?: number | bigint;
-  validatorTrust?: number | bigint;
-  isValidator?: boolean;
+export interface DissolveSubnetParams {
+  subnetId: number;
 }
 
 export class RegistryClient {
@@ -72,7 +71,9 @@ export class RegistryClient {
       this.programId
     );
 
-    const instruction = this.createSubnetInstruction(governor.publicKey, subnetPda, params);
+    const [networkPda] = await findProgramAddress([Buffer.from('network')], this.programId);
+
+    const instruction = this.createSubnetInstruction(governor.publicKey, subnetPda, networkPda, params);
     const transaction = new Transaction().add(instruction);
 
     const signature = await sendAndConfirmTransaction(
@@ -84,6 +85,22 @@ export class RegistryClient {
     return [subnetPda, signature];
   }
 
+  async dissolveSubnet(
+    governor: Keypair,
+    params: DissolveSubnetParams
+  ): Promise<string> {
+    const [subnetPda] = await findProgramAddress(
+      [Buffer.from('subnet'), serializeU16(params.subnetId)],
+      this.programId
+    );
+    const [networkPda] = await findProgramAddress([Buffer.from('network')], this.programId);
+
+    const instruction = this.dissolveSubnetInstruction(governor.publicKey, subnetPda, networkPda, params);
+    const transaction = new Transaction().add(instruction);
+
+    return await sendAndConfirmTransaction(this.connection, transaction, [governor]);
+  }
+
   async registerNeuron(
     hotkey: Keypair,
     coldkey: PublicKey,
@@ -151,28 +168,10 @@ export class RegistryClient {
     return await sendAndConfirmTransaction(this.connection, transaction, [authority]);
   }
 
-  async updateNeuronStatus(
-    authority: Keypair,
-    neuronPda: PublicKey,
-    consensusProgram: PublicKey,
-    stakingProgram: PublicKey,
-    params: UpdateNeuronStatusParams
-  ): Promise<string> {
-    const instruction = this.updateNeuronStatusInstruction(
-      authority.publicKey,
-      neuronPda,
-      consensusProgram,
-      stakingProgram,
-      params
-    );
-    const transaction = new Transaction().add(instruction);
-
-    return await sendAndConfirmTransaction(this.connection, transaction, [authority]);
-  }
-
   private createSubnetInstruction(
     governor: PublicKey,
     subnetPda: PublicKey,
+    networkPda: PublicKey,
     params: CreateSubnetParams
   ): TransactionInstruction {
     const data = Buffer.alloc(1 + 2 + 1 + 1 + 8 + 32);
@@ -191,6 +190,29 @@ export class RegistryClient {
         { pubkey: subnetPda, isSigner: false, isWritable: true },
         { pubkey: governor, isSigner: true, isWritable: true },
         { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: networkPda, isSigner: false, isWritable: true },
+      ],
+      programId: this.programId,
+      data,
+    });
+  }
+
+  private dissolveSubnetInstruction(
+    governor: PublicKey,
+    subnetPda: PublicKey,
+    networkPda: PublicKey,
+    params: DissolveSubnetParams
+  ): TransactionInstruction {
+    const data = Buffer.alloc(1 + 2);
+    let offset = 0;
+    data[offset++] = RegistryInstruction.DissolveSubnet;
+    serializeU16(params.subnetId).copy(data, offset);
+
+    return new TransactionInstruction({
+      keys: [
+        { pubkey: subnetPda, isSigner: false, isWritable: true },
+        { pubkey: governor, isSigner: true, isWritable: true },
+        { pubkey: networkPda, isSigner: false, isWritable: true },
       ],
       programId: this.programId,
       data,
@@ -278,32 +300,6 @@ export class RegistryClient {
     });
   }
 
-  private updateNeuronStatusInstruction(
-    authority: PublicKey,
-    neuronPda: PublicKey,
-    consensusProgram: PublicKey,
-    stakingProgram: PublicKey,
-    params: UpdateNeuronStatusParams
-  ): TransactionInstruction {
-    // Simplified - would need proper optional serialization
-    const data = Buffer.alloc(1 + 9 + 9 + 9 + 9 + 2); // Max size
-    let offset = 0;
-    data[offset++] = RegistryInstruction.UpdateNeuronStatus;
-    // Simplified serialization
-    // ... (would serialize optional fields properly)
-
-    return new TransactionInstruction({
-      keys: [
-        { pubkey: neuronPda, isSigner: false, isWritable: true },
-        { pubkey: authority, isSigner: true, isWritable: false },
-        { pubkey: consensusProgram, isSigner: false, isWritable: false },
-        { pubkey: stakingProgram, isSigner: false, isWritable: false },
-      ],
-      programId: this.programId,
-      data,
-    });
-  }
-
   async getSubnet(subnetPda: PublicKey): Promise<any> {
     const accountInfo = await this.connection.getAccountInfo(subnetPda);
     if (!accountInfo) {
@@ -320,6 +316,23 @@ export class RegistryClient {
       emissionRate: data.readBigUInt64LE(68),
       createdAt: data.readBigInt64LE(76),
       neuronCount: data.readUInt16LE(84),
+      // Subnets created before locking was added are 86 bytes with nothing locked
+      lockedAmount: data.length >= 94 ? data.readBigUInt64LE(86) : BigInt(0),
+    };
+  }
+
+  async getNetwork(): Promise<any> {
+    const [networkPda] = await findProgramAddress([Buffer.from('network')], this.programId);
+    const accountInfo = await this.connection.getAccountInfo(networkPda);
+    if (!accountInfo) {
+      return null;
+    }
+    // Deserialize network lock state
+    const data = accountInfo.data;
+    return {
+      lastLockCost: data.readBigUInt64LE(0),
+      lastLockAt: data.readBigInt64LE(8),
+      totalLocked: data.readBigUInt64LE(16),
     };
   }
 
@@ -31,6 +31,8 @@ async function runTests() {
   hotkey = createKeypair();
   coldkey = createKeypair();
 
+  // Each subnet created locks the network lock cost, which doubles per
+  // creation (1 SOL, then 2 SOL for the two subnets below), plus rent
   await airdropSol(connection, governor.publicKey, 10);
   await airdropSol(connection, hotkey.publicKey, 10);
 
@@ -64,9 +66,30 @@ async function runTests() {
       assert(subnet.validatorLimit === validatorLimit, 'Validator limit should match');
       assert(subnet.emissionRate === emissionRate, 'Emission rate should match');
       assert(subnet.neuronCount === 0, 'Neuron count should be 0');
+      assert(subnet.lockedAmount === BigInt(LAMPORTS_PER_SOL), 'First subnet should lock 1 SOL');
       console.log('   ✓ Subnet created successfully');
     }
 
+    // Test: Re-creating an existing subnet
+    console.log('\n✅ Test: Re-create existing subnet (should fail)');
+    {
+      try {
+        await client.createSubnet(governor, {
+          subnetId: 1,
+          maxNeurons: 255,
+          validatorLimit: 64,
+          emissionRate: BigInt(1000000),
+          incentiveFunctionHash: new Uint8Array(32).fill(0),
+        });
+        assert(false, 'Should have thrown error for existing subnet');
+      } catch (error) {
+        if (error instanceof Error && error.message.startsWith('Assertion failed')) {
+          throw error;
+        }
+        console.log('   ✓ Correctly rejected existing subnet');
+      }
+    }
+
     // Test: Invalid max_neurons
     console.log('\n✅ Test: Invalid max_neurons (should fail)');
     {
@@ -94,18 +117,10 @@ async function runTests() {
     console.log('\n✅ Test: Register Neuron');
     {
       const subnetId = 1;
-      const maxNeurons = 256;
-      const validatorLimit = 64;
-      const emissionRate = BigInt(1000000);
-      const incentiveFunctionHash = new Uint8Array(32).fill(0);
-
-      const [subnetPda] = await client.createSubnet(governor, {
-        subnetId,
-        maxNeurons,
-        validatorLimit,
-        emissionRate,
-        incentiveFunctionHash,
-      });
+      const [subnetPda] = await findProgramAddress(
+        [Buffer.from('subnet'), serializeU16(subnetId)],
+        REGISTRY_PROGRAM_ID
+      );
       const [neuronPda, signature] = await client.registerNeuron(hotkey, coldkey.publicKey, {
         subnetId,
       });
@@ -133,18 +148,10 @@ async function runTests() {
     console.log('\n✅ Test: Sequential UID assignment');
     {
       const subnetId = 1;
-      const maxNeurons = 256;
-      const validatorLimit = 64;
-      const emissionRate = BigInt(1000000);
-      const incentiveFunctionHash = new Uint8Array(32).fill(0);
-
-      const [subnetPda] = await client.createSubnet(governor, {
-        subnetId,
-        maxNeurons,
-        validatorLimit,
-        emissionRate,
-        incentiveFunctionHash,
-      });
+      const [subnetPda] = await findProgramAddress(
+        [Buffer.from('subnet'), serializeU16(subnetId)],
+        REGISTRY_PROGRAM_ID
+      );
       const hotkey2 = createKeypair();
       await airdropSol(connection, hotkey2.publicKey, 10);
 
@@ -1,5 +1,7 @@
 import { PublicKey } from '@solana/web3.js';
 import { serializeU16, serializeU64, deserializeU16, deserializeU64, deserializePubkey } from '../utils/test-utils.js';
+import { RegistryInstruction } from '../clients/registry-client.js';
+import { weightsCommitment } from '../clients/consensus-client.js';
 
 // Test serialization/deserialization logic
 function testSerialization() {
@@ -80,14 +82,66 @@ function testInstructionEncoding() {
     throw new Error(`RegisterNeuron encoding failed: expected length 3, got ${registerData.length}`);
   }
 
+  // Test DissolveSubnet instruction encoding: tag 5, then subnet_id as u16 LE
+  console.log('Testing DissolveSubnet instruction encoding...');
+  const dissolveData = Buffer.alloc(1 + 2);
+  dissolveData[0] = RegistryInstruction.DissolveSubnet;
+  serializeU16(0x0102).copy(dissolveData, 1);
+
+  const expectedDissolve = Buffer.from([0x05, 0x02, 0x01]);
+  if (dissolveData.equals(expectedDissolve)) {
+    console.log('  ✅ DissolveSubnet instruction encoding works');
+  } else {
+    throw new Error(
+      `DissolveSubnet encoding failed: expected ${expectedDissolve.toString('hex')}, got ${dissolveData.toString('hex')}`
+    );
+  }
+
   console.log('\n✅ All instruction encoding tests passed!\n');
 }
 
+// Test the weight commitment preimage matches the consensus program
+function testWeightsCommitment() {
+  console.log('🧪 Testing Weight Commitments\n');
+
+  const validator = new PublicKey(Buffer.from(Array.from({ length: 32 }, (_, i) => i + 1)));
+  const weights = [
+    { minerUid: 1, weight: 6000n },
+    { minerUid: 2, weight: 4000n },
+  ];
+  const salt = Buffer.alloc(32, 9);
+
+  console.log('Testing commitment preimage layout...');
+  const commitment = weightsCommitment(validator, 7, 42, weights, salt);
+  const expected = 'dec7840e5d62380993d6ad108b9832f98c118e74807833c1df93227887d05d1d';
+  if (commitment.toString('hex') === expected) {
+    console.log('  ✅ Commitment matches the expected hash');
+  } else {
+    throw new Error(`Commitment mismatch: expected ${expected}, got ${commitment.toString('hex')}`);
+  }
+
+  console.log('Testing commitment binds validator, subnet and epoch...');
+  const otherValidator = new PublicKey(Buffer.alloc(32, 1));
+  const variants = [
+    weightsCommitment(otherValidator, 7, 42, weights, salt),
+    weightsCommitment(validator, 8, 42, weights, salt),
+    weightsCommitment(validator, 7, 43, weights, salt),
+  ];
+  if (variants.every((variant) => !variant.equals(commitment))) {
+    console.log('  ✅ Commitment changes with validator, subnet and epoch');
+  } else {
+    throw new Error('Commitment did not change with validator, subnet or epoch');
+  }
+
+  console.log('\n✅ All weight commitment tests passed!\n');
+}
+
 // Run all unit tests
 function runUnitTests() {
   try {
     testSerialization();
     testInstructionEncoding();
+    testWeightsCommitment();
     console.log('🎉 All unit tests passed!\n');
     return true;
   } catch (error) {
@@ -100,5 +154,5 @@ if (import.meta.url === `file://${process.argv[1]}` || process.argv[1]?.includes
   runUnitTests();
 }
 
-export { runUnitTests, testSerialization, testInstructionEncoding };
+export { runUnitTests, testSerialization, testInstructionEncoding, testWeightsCommitment };
 